@@ -0,0 +1,91 @@
+//! Additive animation generation against a reference pose
+//!
+//! Every pose becomes its offset from the reference: the translation difference, and the
+//! rotation that takes the reference rotation to the pose rotation. Played additively on
+//! top of another animation, the result reproduces the authored motion relative to the
+//! reference.
+
+use std::collections::{BTreeSet, HashMap};
+
+use log::{debug, warn};
+use rbx_types::CFrame;
+
+use crate::types::Keyframe;
+use crate::utils::{cframe_from_glam, cframe_to_glam, collect_pose_tracks, sample_pose_track};
+
+/// The pose subtracted from every keyframe
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdditiveReference {
+    /// The pose at the keyframe with this index in time order, `0` being the first
+    Frame(usize),
+    /// Explicit per-bone poses, e.g. taken from another file with [`reference_pose`]
+    Poses(HashMap<String, CFrame>),
+}
+
+impl Default for AdditiveReference {
+    fn default() -> Self {
+        Self::Frame(0)
+    }
+}
+
+/// Every bone's pose at the keyframe with index `frame` in time order
+///
+/// Bones without a pose at that keyframe are sampled from their neighbouring poses.
+/// Returns an empty map if there is no such keyframe.
+pub fn reference_pose(keyframes: &[Keyframe], frame: usize) -> HashMap<String, CFrame> {
+    let mut times: Vec<f64> = keyframes.iter().map(|kf| kf.time).collect();
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+
+    let Some(&time) = times.get(frame) else {
+        warn!(
+            "Additive reference frame {} is out of range ({} keyframes)",
+            frame,
+            times.len()
+        );
+        return HashMap::new();
+    };
+
+    collect_pose_tracks(keyframes)
+        .into_iter()
+        .map(|(name, track)| {
+            let (position, rotation) = sample_pose_track(&track, time);
+            (name.to_owned(), cframe_from_glam(position, rotation))
+        })
+        .collect()
+}
+
+/// Replace every pose with its offset from the reference pose
+///
+/// Bones missing from the reference are left unchanged.
+pub fn make_additive(keyframes: &mut [Keyframe], reference: &AdditiveReference) {
+    let reference = match reference {
+        AdditiveReference::Frame(frame) => reference_pose(keyframes, *frame),
+        AdditiveReference::Poses(poses) => poses.clone(),
+    };
+    let reference: HashMap<&str, _> = reference
+        .iter()
+        .map(|(name, cframe)| (name.as_str(), cframe_to_glam(cframe)))
+        .collect();
+
+    let mut unreferenced = BTreeSet::new();
+    for keyframe in keyframes.iter_mut() {
+        for pose in &mut keyframe.poses {
+            let Some(&(reference_position, reference_rotation)) = reference.get(pose.name.as_str())
+            else {
+                unreferenced.insert(pose.name.clone());
+                continue;
+            };
+
+            let (position, rotation) = cframe_to_glam(&pose.cframe);
+            pose.cframe = cframe_from_glam(
+                position - reference_position,
+                (reference_rotation.inverse() * rotation).normalize(),
+            );
+        }
+    }
+
+    for name in unreferenced {
+        debug!("Bone {} has no reference pose; left unchanged", name);
+    }
+}
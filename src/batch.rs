@@ -0,0 +1,304 @@
+//! Finding the inputs of a batch conversion and naming its outputs
+//!
+//! Inputs are files, directories searched recursively for animation files, or glob
+//! patterns. Each input remembers its path below the directory it was found in, so outputs
+//! can mirror the source tree. Output names come from a template such as `{stem}_{clip}`,
+//! with `.rbxm` appended.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, bail};
+
+/// File extensions picked up when searching directories
+pub const ANIMATION_EXTENSIONS: &[&str] = &[
+    "fbx", "dae", "gltf", "glb", "bvh", "x", "smd", "ms3d", "b3d", "md5anim",
+];
+
+/// Whether a path has one of the [`ANIMATION_EXTENSIONS`]
+pub fn is_animation_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ANIMATION_EXTENSIONS
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// A file to convert
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BatchInput {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Path of the file below the directory or glob base it was found in
+    pub relative: PathBuf,
+}
+
+impl BatchInput {
+    /// The file name without its extension
+    pub fn stem(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// The extension without its dot, or an empty string
+    pub fn extension(&self) -> String {
+        self.path
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// Expand files, directories and glob patterns into the files to convert
+///
+/// Directories are searched recursively for animation files; glob patterns keep every file
+/// they match. Inputs keep the order they were given in, with the files each one finds
+/// sorted. Files found through several inputs are converted once. Inputs that match nothing
+/// are an error, as they are usually typos.
+pub fn collect_inputs(specs: &[String]) -> Result<Vec<BatchInput>> {
+    let mut inputs = Vec::new();
+    let mut seen = HashSet::new();
+    for spec in specs {
+        let path = Path::new(spec);
+        let mut found: Vec<BatchInput> = if path.is_dir() {
+            let mut files = Vec::new();
+            find_animation_files(path, &mut files)
+                .with_context(|| format!("Failed to search {}", spec))?;
+            files
+                .into_iter()
+                .map(|file| relative_input(file, path))
+                .collect()
+        } else if path.is_file() {
+            vec![BatchInput {
+                path: path.to_path_buf(),
+                relative: PathBuf::from(path.file_name().unwrap_or_default()),
+            }]
+        } else if is_glob(spec) {
+            let base = glob_base(spec);
+            glob::glob(spec)
+                .with_context(|| format!("Invalid pattern {}", spec))?
+                .filter_map(|entry| entry.ok())
+                .filter(|file| file.is_file())
+                .map(|file| relative_input(file, &base))
+                .collect()
+        } else {
+            bail!("{} does not exist", spec);
+        };
+
+        if found.is_empty() {
+            bail!("{} matched no animation files", spec);
+        }
+        // The same file reached through overlapping inputs keeps its first relative path
+        found.sort();
+        inputs.extend(
+            found
+                .into_iter()
+                .filter(|input| seen.insert(input.path.clone())),
+        );
+    }
+    Ok(inputs)
+}
+
+fn find_animation_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            find_animation_files(&path, files)?;
+        } else if is_animation_file(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn relative_input(path: PathBuf, base: &Path) -> BatchInput {
+    let relative = path
+        .strip_prefix(base)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| PathBuf::from(path.file_name().unwrap_or_default()));
+    BatchInput { path, relative }
+}
+
+fn is_glob(spec: &str) -> bool {
+    spec.contains(['*', '?', '['])
+}
+
+/// The leading directories of a glob pattern that contain no wildcards
+fn glob_base(pattern: &str) -> PathBuf {
+    let mut base = PathBuf::new();
+    for component in Path::new(pattern).components() {
+        if let Component::Normal(part) = component
+            && is_glob(&part.to_string_lossy())
+        {
+            break;
+        }
+        base.push(component);
+    }
+    base
+}
+
+/// Clip name that stands in for every clip when comparing inputs' outputs
+const PROBE_CLIP: &str = "clip";
+
+/// A file name template with `{stem}`, `{clip}` and `{ext}` placeholders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTemplate {
+    template: String,
+}
+
+impl Default for OutputTemplate {
+    fn default() -> Self {
+        Self {
+            template: "{stem}".to_owned(),
+        }
+    }
+}
+
+impl OutputTemplate {
+    /// Parse a template, rejecting unknown placeholders and path separators
+    pub fn new(template: &str) -> Result<Self> {
+        let mut rest = template;
+        while let Some(open) = rest.find('{') {
+            let Some(close) = rest[open..].find('}') else {
+                bail!("Unclosed placeholder in output template '{}'", template);
+            };
+            let placeholder = &rest[open + 1..open + close];
+            if !matches!(placeholder, "stem" | "clip" | "ext") {
+                bail!(
+                    "Unknown placeholder {{{}}} in output template '{}'",
+                    placeholder,
+                    template
+                );
+            }
+            rest = &rest[open + close + 1..];
+        }
+        if template.contains(['/', '\\']) {
+            bail!("Output template '{}' must be a file name", template);
+        }
+        if template.is_empty() {
+            bail!("Output template is empty");
+        }
+        Ok(Self {
+            template: template.to_owned(),
+        })
+    }
+
+    /// Whether the template names one output per animation clip
+    pub fn uses_clip(&self) -> bool {
+        self.template.contains("{clip}")
+    }
+
+    /// The output file name for an input and clip, with `.rbxm` appended
+    ///
+    /// Characters that are not allowed in file names are replaced with `_`.
+    pub fn render(&self, input: &BatchInput, clip: &str) -> String {
+        let name = self
+            .template
+            .replace("{stem}", &input.stem())
+            .replace("{clip}", clip)
+            .replace("{ext}", &input.extension());
+        let name: String = name
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .collect();
+        format!("{}.rbxm", name)
+    }
+
+    /// Where an input's clip is written below `out_dir`, keeping the input's directories
+    pub fn output_path(&self, out_dir: &Path, input: &BatchInput, clip: &str) -> PathBuf {
+        let directory = input.relative.parent().unwrap_or(Path::new(""));
+        out_dir.join(directory).join(self.render(input, clip))
+    }
+
+    /// Fail if two inputs would be written to the same path
+    ///
+    /// With `{clip}`, inputs whose names match for one clip name clash on every clip they
+    /// share, so they are caught here; clashes that depend on the clip names themselves
+    /// are caught by [`OutputClaims`] once the clips are known.
+    pub fn check_clashes(&self, out_dir: &Path, inputs: &[BatchInput]) -> Result<()> {
+        let mut outputs: HashMap<PathBuf, &BatchInput> = HashMap::new();
+        for input in inputs {
+            let output = self.output_path(out_dir, input, PROBE_CLIP);
+            if let Some(other) = outputs.insert(output, input) {
+                bail!(
+                    "{} and {} would be written to the same outputs; add {{ext}} to the name",
+                    other.path.display(),
+                    input.path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The output paths of an input's clips, in order
+    ///
+    /// Fails if two clips would be written to the same path, e.g. clip names that only
+    /// differ in characters replaced by [`Self::render`].
+    pub fn output_paths(
+        &self,
+        out_dir: &Path,
+        input: &BatchInput,
+        clips: &[&str],
+    ) -> Result<Vec<PathBuf>> {
+        let mut outputs: Vec<PathBuf> = Vec::with_capacity(clips.len());
+        for clip in clips {
+            let output = self.output_path(out_dir, input, clip);
+            if let Some(index) = outputs.iter().position(|other| *other == output) {
+                bail!(
+                    "Clips '{}' and '{}' of {} would both be written to {}",
+                    clips[index],
+                    clip,
+                    input.path.display(),
+                    output.display()
+                );
+            }
+            outputs.push(output);
+        }
+        Ok(outputs)
+    }
+}
+
+/// The output paths each input writes, shared by the threads of a batch
+///
+/// An input claims all its outputs before writing any of them, so two inputs can never
+/// write the same file.
+#[derive(Debug, Default)]
+pub struct OutputClaims {
+    owners: Mutex<HashMap<PathBuf, PathBuf>>,
+}
+
+impl OutputClaims {
+    /// Claim outputs for an input, failing without claiming any if another input owns one
+    pub fn claim(&self, input: &BatchInput, outputs: &[PathBuf]) -> Result<()> {
+        let mut owners = self.owners.lock().unwrap();
+        for output in outputs {
+            if let Some(owner) = owners.get(output)
+                && *owner != input.path
+            {
+                bail!(
+                    "{} is already written by {}",
+                    output.display(),
+                    owner.display()
+                );
+            }
+        }
+        for output in outputs {
+            owners.insert(output.clone(), input.path.clone());
+        }
+        Ok(())
+    }
+
+    /// Release the outputs of inputs for which `keep` returns false
+    pub fn retain(&self, keep: impl Fn(&Path) -> bool) {
+        self.owners.lock().unwrap().retain(|_, owner| keep(owner));
+    }
+}
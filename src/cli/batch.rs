@@ -0,0 +1,165 @@
+//! The `convert` subcommand: many inputs converted in parallel into an output directory
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use anyhow::{Context, Result, bail};
+use log::{error, info, warn};
+
+use anim2rbx::AnimationConverter;
+use anim2rbx::batch::{BatchInput, OutputClaims, collect_inputs};
+
+use crate::{BatchArgs, build_converter, write_sequence};
+
+/// Convert every input, then list the failures and fail if there were any
+pub fn run(args: &BatchArgs) -> Result<()> {
+    let converter = build_converter(&args.options)?;
+    let inputs = collect_inputs(&args.inputs)?;
+    let out_dir = Path::new(&args.out_dir);
+
+    args.name.check_clashes(out_dir, &inputs)?;
+
+    let claims = OutputClaims::default();
+    let results = convert_inputs(&converter, &inputs, args, out_dir, &claims);
+
+    let mut outputs = 0;
+    let mut failures = Vec::new();
+    for (input, result) in inputs.iter().zip(results) {
+        match result {
+            Ok(written) => outputs += written.len(),
+            Err(err) => failures.push((input, err)),
+        }
+    }
+
+    info!(
+        "Converted {} of {} files ({} outputs)",
+        inputs.len() - failures.len(),
+        inputs.len(),
+        outputs
+    );
+    if failures.is_empty() {
+        return Ok(());
+    }
+    for (input, err) in &failures {
+        error!("  {}: {:#}", input.path.display(), err);
+    }
+    bail!("{} of {} files failed", failures.len(), inputs.len())
+}
+
+/// Convert inputs on `--jobs` threads, returning each input's result in input order
+pub fn convert_inputs(
+    converter: &AnimationConverter,
+    inputs: &[BatchInput],
+    args: &BatchArgs,
+    out_dir: &Path,
+    claims: &OutputClaims,
+) -> Vec<Result<Vec<PathBuf>>> {
+    let jobs = args
+        .jobs
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |count| count.get()))
+        .clamp(1, inputs.len().max(1));
+    info!(
+        "Converting {} files into {} ({} jobs)",
+        inputs.len(),
+        out_dir.display(),
+        jobs
+    );
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(inputs.len()));
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input) = inputs.get(index) else {
+                        break;
+                    };
+                    let result = convert_input(converter, input, args, out_dir, claims);
+                    if let Err(err) = &result {
+                        warn!("{}: {:#}", input.path.display(), err);
+                    }
+                    results.lock().unwrap().push((index, result));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Convert one input and write its outputs, returning their paths
+///
+/// Nothing is written unless the input can claim all its outputs. Clips over budget are
+/// skipped and the others written, and the input then fails listing the skipped outputs.
+pub fn convert_input(
+    converter: &AnimationConverter,
+    input: &BatchInput,
+    args: &BatchArgs,
+    out_dir: &Path,
+    claims: &OutputClaims,
+) -> Result<Vec<PathBuf>> {
+    let path = input
+        .path
+        .to_str()
+        .with_context(|| format!("{} is not valid UTF-8", input.path.display()))?;
+    let sequences = if args.name.uses_clip() {
+        converter.convert_file_per_animation(path)?
+    } else {
+        let (kfs, report) = converter.convert_file_with_report(path)?;
+        vec![(String::new(), kfs, report)]
+    };
+
+    let clips: Vec<&str> = sequences.iter().map(|(clip, _, _)| clip.as_str()).collect();
+    let outputs = args.name.output_paths(out_dir, input, &clips)?;
+    claims.claim(input, &outputs)?;
+
+    let mut written = Vec::new();
+    let mut failed = Vec::new();
+    for ((_, kfs, report), output) in sequences.into_iter().zip(outputs) {
+        if let Some(validation) = &report.validation
+            && !validation.passed()
+        {
+            let violations: Vec<String> = validation
+                .violations
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            if !args.options.budget_warn_only {
+                warn!(
+                    "{} exceeded its budget: {}; not written",
+                    output.display(),
+                    violations.join("; ")
+                );
+                failed.push(output.display().to_string());
+                continue;
+            }
+            warn!(
+                "{} exceeded its budget: {}",
+                output.display(),
+                violations.join("; ")
+            );
+        }
+
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        write_sequence(&output, &kfs)?;
+        info!("{} -> {}", input.path.display(), output.display());
+        written.push(output);
+    }
+
+    if !failed.is_empty() {
+        bail!(
+            "{} exceeded its budget: {}",
+            input.path.display(),
+            failed.join(", ")
+        );
+    }
+    Ok(written)
+}
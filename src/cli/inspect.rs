@@ -0,0 +1,84 @@
+//! The `inspect` subcommand: describing a source file before converting it
+
+use anyhow::Result;
+
+use anim2rbx::inspect::{Inspection, inspect_file};
+
+use crate::InspectArgs;
+
+/// Print the inspection of a file as text or JSON
+pub fn run(args: &InspectArgs) -> Result<()> {
+    let inspection = inspect_file(&args.file)?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&inspection)?);
+    } else {
+        print_text(&args.file, &inspection);
+    }
+    Ok(())
+}
+
+fn print_text(file: &str, inspection: &Inspection) {
+    println!("{}", file);
+
+    println!("\nClips ({}):", inspection.clips.len());
+    for clip in &inspection.clips {
+        let rate = if clip.ticks_per_second > 0.0 {
+            format!("{} ticks/s", clip.ticks_per_second)
+        } else {
+            "ticks/s unset".to_owned()
+        };
+        println!(
+            "  {}  {:.3}s  ({} ticks, {}, {} channels)",
+            clip.name,
+            clip.duration,
+            clip.duration_ticks,
+            rate,
+            clip.channels.len()
+        );
+    }
+
+    println!("\nHierarchy (* animated, + skinned bone):");
+    if let Some(root) = &inspection.root {
+        for (depth, node) in root.walk() {
+            println!(
+                "  {}{}{} {}",
+                "  ".repeat(depth),
+                if node.animated { "*" } else { " " },
+                if node.bone { "+" } else { " " },
+                node.name
+            );
+        }
+    }
+
+    for clip in &inspection.clips {
+        println!(
+            "\nChannels of {} (position / rotation / scale keys):",
+            clip.name
+        );
+        let width = clip
+            .channels
+            .iter()
+            .map(|channel| channel.node.len())
+            .max()
+            .unwrap_or(0);
+        for channel in &clip.channels {
+            println!(
+                "  {:width$}  {:>5} {:>5} {:>5}",
+                channel.node,
+                channel.position_keys,
+                channel.rotation_keys,
+                channel.scaling_keys,
+                width = width
+            );
+        }
+    }
+
+    if inspection.problems.is_empty() {
+        println!("\nNo problems found");
+    } else {
+        println!("\nProblems ({}):", inspection.problems.len());
+        for problem in &inspection.problems {
+            println!("  {}", problem);
+        }
+    }
+}
@@ -4,65 +4,859 @@ use std::fs::File;
 use std::io::BufWriter;
 use std::path::Path;
 
-use anyhow::Result;
-use clap::Parser;
-use log::{debug, info};
+use anyhow::{Context, Result, bail};
+use clap::{Parser, Subcommand, ValueEnum};
+use log::{debug, info, warn};
 
-use anim2rbx::AnimationConverter;
+use anim2rbx::additive::{self, AdditiveReference};
+use anim2rbx::batch::OutputTemplate;
+use anim2rbx::clips::{ClipRanges, ClipSource, ConcatConfig};
+use anim2rbx::looping::{LoopConfig, LoopSeam};
+use anim2rbx::mirror::{MirrorConfig, MirrorPlane};
+use anim2rbx::optimize::Quantization;
+use anim2rbx::remap::BoneMap;
+use anim2rbx::rest_pose::RestPoseSource;
+use anim2rbx::retarget::Preset;
+use anim2rbx::root_motion::RootMotionConfig;
+use anim2rbx::selection::BoneSelection;
+use anim2rbx::smoothing::SmoothingConfig;
+use anim2rbx::timing::TimeOperation;
+use anim2rbx::validation::Budget;
+use anim2rbx::{AnimationConverter, ConversionReport, Tolerance};
+use rbx_dom_weak::WeakDom;
+
+mod batch;
+mod inspect;
+mod watch;
 
 /// Convert animation files to Roblox KeyframeSequence format
 #[derive(Parser)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    args: Args,
+
+    /// Enable verbose logging
+    #[arg(short = 'v', long = "verbose", global = true)]
+    verbose: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert many files in parallel into an output directory
+    Convert(BatchArgs),
+    /// Convert files, then convert them again whenever they change
+    Watch(WatchArgs),
+    /// Describe the clips, hierarchy, channels and problems of an animation file
+    Inspect(InspectArgs),
+}
+
+/// Converting a single file
+#[derive(clap::Args)]
 struct Args {
     /// Input animation file (FBX, COLLADA, etc.)
-    input: String,
+    #[arg(required = true)]
+    input: Option<String>,
 
     /// Output .rbxm file
     #[arg(short = 'o', long = "output", value_name = "FILE")]
     output: Option<String>,
 
+    /// Split the input into one sequence per clip in this ranges file (TOML or JSON)
+    #[arg(long = "split", value_name = "FILE", conflicts_with_all = ["append", "root_motion_out"])]
+    split: Option<String>,
+
+    /// Directory the clips of --split are written to, as <clip>.rbxm
+    #[arg(
+        long = "split-dir",
+        requires = "split",
+        default_value = ".",
+        value_name = "DIR"
+    )]
+    split_dir: String,
+
+    /// Convert only the animation with this name from the input
+    #[arg(long = "animation", value_name = "NAME")]
+    animation: Option<String>,
+
+    /// Append another clip after the input, as FILE or FILE#ANIMATION (repeatable)
+    #[arg(long = "append", value_name = "CLIP")]
+    append: Vec<String>,
+
+    /// Seconds of space between appended clips
+    #[arg(
+        long = "gap",
+        requires = "append",
+        default_value = "0",
+        value_name = "SECONDS"
+    )]
+    gap: f64,
+
+    /// Seconds over which appended clips blend into the previous clip
+    #[arg(
+        long = "crossfade",
+        requires = "append",
+        default_value = "0",
+        value_name = "SECONDS"
+    )]
+    crossfade: f64,
+
+    /// Write the extracted root motion as a Luau module, or JSON for a .json path
+    #[arg(long = "root-motion-out", requires = "in_place", value_name = "FILE")]
+    root_motion_out: Option<String>,
+
+    #[command(flatten)]
+    options: ConvertOptions,
+}
+
+/// Converting many files at once
+#[derive(clap::Args)]
+struct BatchArgs {
+    /// Input files, directories (searched recursively) or glob patterns
+    #[arg(required = true, value_name = "INPUT")]
+    inputs: Vec<String>,
+
+    /// Directory to write outputs to, mirroring the directories of the inputs
+    #[arg(long = "out-dir", value_name = "DIR")]
+    out_dir: String,
+
+    /// Output file name: {stem} and {ext} of the input, {clip} for one output per animation
+    #[arg(
+        long = "name",
+        value_name = "TEMPLATE",
+        default_value = "{stem}",
+        value_parser = OutputTemplate::new
+    )]
+    name: OutputTemplate,
+
+    /// Number of files converted at once (defaults to the number of CPUs)
+    #[arg(short = 'j', long = "jobs", value_name = "COUNT")]
+    jobs: Option<usize>,
+
+    #[command(flatten)]
+    options: ConvertOptions,
+}
+
+/// Reconverting files as they change
+#[derive(clap::Args)]
+struct WatchArgs {
+    #[command(flatten)]
+    batch: BatchArgs,
+
+    /// Milliseconds a file must stay unchanged before it is converted
+    #[arg(long = "debounce", value_name = "MS", default_value = "500")]
+    debounce: u64,
+}
+
+/// Describing a source file
+#[derive(clap::Args)]
+struct InspectArgs {
+    /// Animation file to inspect
+    file: String,
+
+    /// Print the inspection as JSON
+    #[arg(long = "json")]
+    json: bool,
+}
+
+/// Options shared by every conversion
+#[derive(clap::Args)]
+struct ConvertOptions {
+    /// Where bone rest poses come from
+    #[arg(long = "rest-pose", value_enum, default_value_t = RestPoseArg::Node)]
+    rest_pose: RestPoseArg,
+
+    /// Take bone rest poses from the node transforms of another file (e.g. a T-pose export)
+    #[arg(
+        long = "rest-pose-file",
+        value_name = "FILE",
+        conflicts_with = "rest_pose"
+    )]
+    rest_pose_file: Option<String>,
+
+    /// Keep only these bones and their descendants (glob, or regex prefixed with re:)
+    #[arg(long = "include-bones", value_name = "PATTERN")]
+    include_bones: Vec<String>,
+
+    /// Drop these bones, reparenting their children (glob, or regex prefixed with re:)
+    #[arg(long = "exclude-bones", value_name = "PATTERN")]
+    exclude_bones: Vec<String>,
+
+    /// Bone mapping file (TOML or JSON) with exact names and regex rules
+    #[arg(long = "bone-map", value_name = "FILE")]
+    bone_map: Option<String>,
+
+    /// Mirror the animation, swapping left and right bones
+    #[arg(long = "mirror")]
+    mirror: bool,
+
+    /// Axis flipped by --mirror, in the source skeleton's model space
+    #[arg(long = "mirror-axis", value_enum, default_value_t = AxisArg::X)]
+    mirror_axis: AxisArg,
+
+    /// Name fragments of counterpart bones, as LEFT:RIGHT (repeatable, replaces the defaults)
+    #[arg(long = "mirror-pair", value_name = "LEFT:RIGHT", value_parser = parse_pair)]
+    mirror_pairs: Vec<(String, String)>,
+
+    /// Retarget the animation onto a stock rig
+    #[arg(long = "preset", value_enum)]
+    preset: Option<PresetArg>,
+
+    /// Override the preset's source-units-to-studs scale
+    #[arg(long = "preset-scale", requires = "preset", value_name = "SCALE")]
+    preset_scale: Option<f32>,
+
+    /// Smooth every bone track with a Gaussian of this many poses (standard deviation)
+    #[arg(long = "smooth", value_name = "STRENGTH")]
+    smooth: Option<f32>,
+
+    /// Smoothing strength for one bone, as NAME=STRENGTH (repeatable, 0 disables)
+    #[arg(long = "smooth-bone", value_name = "NAME=STRENGTH", value_parser = parse_bone_strength)]
+    smooth_bones: Vec<(String, f32)>,
+
+    /// Replace single-pose spikes with the interpolation of their neighbours
+    #[arg(long = "despike")]
+    despike: bool,
+
+    /// Position deviation in studs above which --despike treats a pose as a spike
+    #[arg(long = "despike-position-tolerance", default_value = "0.5")]
+    despike_position_tolerance: f32,
+
+    /// Rotation deviation in degrees above which --despike treats a pose as a spike
+    #[arg(long = "despike-angle-tolerance", default_value = "30.0")]
+    despike_angle_tolerance: f32,
+
+    /// Strip horizontal root motion so the clip plays in place
+    #[arg(long = "in-place")]
+    in_place: bool,
+
+    /// Also strip vertical root motion (requires --in-place)
+    #[arg(long = "in-place-vertical", requires = "in_place")]
+    in_place_vertical: bool,
+
+    /// Also strip root rotation about the vertical axis (requires --in-place)
+    #[arg(long = "in-place-yaw", requires = "in_place")]
+    in_place_yaw: bool,
+
+    /// Root bone to extract motion from (defaults to the top-most animated bone)
+    #[arg(long = "root-bone", requires = "in_place", value_name = "NAME")]
+    root_bone: Option<String>,
+
+    /// Make the clip additive by subtracting a reference pose from every pose
+    #[arg(long = "additive")]
+    additive: bool,
+
+    /// Keyframe index of the reference pose used by --additive
+    #[arg(
+        long = "additive-frame",
+        requires = "additive",
+        default_value = "0",
+        value_name = "INDEX"
+    )]
+    additive_frame: usize,
+
+    /// Take the reference pose from another animation file instead of this clip
+    #[arg(
+        long = "additive-reference",
+        requires = "additive",
+        value_name = "FILE"
+    )]
+    additive_reference: Option<String>,
+
+    /// Play the clip backwards
+    #[arg(long = "reverse")]
+    reverse: bool,
+
+    /// Play the clip forwards, then backwards (after --reverse)
+    #[arg(long = "ping-pong")]
+    ping_pong: bool,
+
+    /// Multiply keyframe times by this factor (2 plays at half speed)
+    #[arg(long = "time-scale", value_name = "FACTOR", value_parser = parse_positive)]
+    time_scale: Option<f64>,
+
+    /// Stretch the clip to this many seconds
+    #[arg(
+        long = "duration",
+        value_name = "SECONDS",
+        value_parser = parse_positive,
+        conflicts_with = "time_scale"
+    )]
+    duration: Option<f64>,
+
     /// Don't filter out bones with identical poses
     #[arg(long = "no-filter")]
     no_filter: bool,
 
-    /// Epsilon value for floating-point comparisons
-    #[arg(long = "epsilon", default_value = "0.00001")]
-    epsilon: f32,
+    /// Filter out bones whose poses all stay at the rest pose
+    #[arg(long = "filter-rest-bones")]
+    filter_rest_bones: bool,
 
-    /// Enable verbose logging
-    #[arg(short = 'v', long = "verbose")]
-    verbose: bool,
+    /// Translation tolerance in studs for pose comparisons
+    #[arg(
+        long = "position-tolerance",
+        alias = "epsilon",
+        default_value = "0.00001"
+    )]
+    position_tolerance: f32,
+
+    /// Rotation tolerance in degrees for pose comparisons
+    #[arg(long = "angle-tolerance", default_value = "0.001")]
+    angle_tolerance: f32,
+
+    /// Detect whether the clip loops and set the sequence's Loop property accordingly
+    #[arg(long = "loop")]
+    loop_mode: bool,
+
+    /// Mark the sequence as looping even if its start and end poses differ
+    #[arg(long = "force-loop", requires = "loop_mode")]
+    force_loop: bool,
+
+    /// How to repair the seam of a looping clip
+    #[arg(
+        long = "loop-seam",
+        value_enum,
+        default_value_t = SeamArg::None,
+        requires = "loop_mode"
+    )]
+    loop_seam: SeamArg,
+
+    /// Number of trailing frames to blend over with --loop-seam blend
+    #[arg(
+        long = "loop-blend-frames",
+        default_value = "5",
+        requires = "loop_mode"
+    )]
+    loop_blend_frames: usize,
+
+    /// Largest start/end position difference in studs that still counts as a loop
+    #[arg(long = "loop-position-tolerance", default_value = "0.05")]
+    loop_position_tolerance: f32,
+
+    /// Largest start/end rotation difference in degrees that still counts as a loop
+    #[arg(long = "loop-angle-tolerance", default_value = "5.0")]
+    loop_angle_tolerance: f32,
+
+    /// Round pose values to a fixed precision before filtering
+    #[arg(long = "quantize")]
+    quantize: bool,
+
+    /// Position step in studs used by --quantize
+    #[arg(long = "quantize-position-step", default_value = "0.001")]
+    quantize_position_step: f32,
+
+    /// Rotation step in degrees used by --quantize
+    #[arg(long = "quantize-angle-step", default_value = "0.01")]
+    quantize_angle_step: f32,
+
+    /// Drop poses that linear interpolation can rebuild within tolerance
+    #[arg(long = "reduce")]
+    reduce: bool,
+
+    /// Maximum position error in studs allowed by --reduce
+    #[arg(long = "reduce-position-tolerance", default_value = "0.01")]
+    reduce_position_tolerance: f32,
+
+    /// Maximum rotation error in degrees allowed by --reduce
+    #[arg(long = "reduce-angle-tolerance", default_value = "0.5")]
+    reduce_angle_tolerance: f32,
+
+    /// Print keyframe, pose and size statistics of the output sequence
+    #[arg(long = "stats")]
+    stats: bool,
+
+    /// Fail if the sequence has more keyframes than this
+    #[arg(long = "max-keyframes", value_name = "COUNT")]
+    max_keyframes: Option<usize>,
+
+    /// Fail if the sequence has more poses than this
+    #[arg(long = "max-poses", value_name = "COUNT")]
+    max_poses: Option<usize>,
+
+    /// Fail if the sequence is longer than this many seconds
+    #[arg(long = "max-duration", value_name = "SECONDS")]
+    max_duration: Option<f64>,
+
+    /// Fail if the serialized sequence is larger than this (e.g. 500000, 200KB, 1.5MB)
+    #[arg(long = "max-size", value_name = "SIZE", value_parser = parse_size)]
+    max_size: Option<usize>,
+
+    /// Fail if a single keyframe has more poses than this
+    #[arg(long = "max-bones-per-keyframe", value_name = "COUNT")]
+    max_bones_per_keyframe: Option<usize>,
+
+    /// Only warn when the sequence exceeds a budget
+    #[arg(long = "budget-warn-only")]
+    budget_warn_only: bool,
+}
+
+/// Model-space axes
+#[derive(Clone, Copy, ValueEnum)]
+enum AxisArg {
+    X,
+    Y,
+    Z,
+}
+
+/// Rest pose sources
+#[derive(Clone, Copy, ValueEnum)]
+enum RestPoseArg {
+    /// The node transforms of the input file
+    Node,
+    /// The bind pose stored in the mesh bones' offset matrices
+    Bind,
+    /// The first keyframe of each bone
+    FirstFrame,
+}
+
+/// Built-in retargeting presets
+#[derive(Clone, Copy, ValueEnum)]
+enum PresetArg {
+    /// Mixamo skeletons onto the R15 rig
+    MixamoR15,
+    /// Mixamo skeletons onto the R6 rig
+    MixamoR6,
+}
+
+/// Seam repair strategies for looping clips
+#[derive(Clone, Copy, ValueEnum)]
+enum SeamArg {
+    /// Leave the seam untouched
+    None,
+    /// Make each bone's last pose equal its first pose
+    Match,
+    /// Blend the seam offset over the last --loop-blend-frames frames
+    Blend,
+}
+
+/// Parse a LEFT:RIGHT name pair
+fn parse_pair(value: &str) -> Result<(String, String), String> {
+    match value.split_once(':') {
+        Some((left, right)) if !left.is_empty() && !right.is_empty() => {
+            Ok((left.to_owned(), right.to_owned()))
+        }
+        _ => Err(format!("expected LEFT:RIGHT, got '{}'", value)),
+    }
+}
+
+/// Parse a NAME=STRENGTH bone smoothing strength
+fn parse_bone_strength(value: &str) -> Result<(String, f32), String> {
+    let (name, strength) = value
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected NAME=STRENGTH, got '{}'", value))?;
+    let strength = strength
+        .parse::<f32>()
+        .ok()
+        .filter(|strength| *strength >= 0.0)
+        .ok_or_else(|| format!("invalid smoothing strength '{}'", strength))?;
+    Ok((name.to_owned(), strength))
+}
+
+/// Parse a number greater than zero
+fn parse_positive(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number > 0.0 && number.is_finite() => Ok(number),
+        _ => Err(format!("expected a positive number, got '{}'", value)),
+    }
+}
+
+/// Parse a byte size with an optional KB/MB (or KiB/MiB) suffix
+fn parse_size(value: &str) -> Result<usize, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let multiplier = match unit.to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "kb" => 1e3,
+        "mb" => 1e6,
+        "kib" => 1024.0,
+        "mib" => 1024.0 * 1024.0,
+        _ => return Err(format!("unknown size unit '{}'", unit)),
+    };
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", value))?;
+    Ok((number * multiplier).round() as usize)
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
+    let cli = Cli::parse();
 
     // Initialize logger based on verbose flag
-    let log_level = if args.verbose { "debug" } else { "info" };
+    let log_level = if cli.verbose { "debug" } else { "info" };
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level)).init();
 
-    let output_file = args.output.as_deref().unwrap_or_else(|| {
+    match &cli.command {
+        Some(Command::Convert(batch_args)) => batch::run(batch_args),
+        Some(Command::Watch(watch_args)) => watch::run(watch_args),
+        Some(Command::Inspect(inspect_args)) => inspect::run(inspect_args),
+        None => convert_single(&cli.args),
+    }
+}
+
+/// Configure a converter from the shared conversion options
+fn build_converter(options: &ConvertOptions) -> Result<AnimationConverter> {
+    debug!("Filter identical bones: {}", !options.no_filter);
+    debug!("Filter rest pose bones: {}", options.filter_rest_bones);
+    debug!("Position tolerance: {} studs", options.position_tolerance);
+    debug!("Angle tolerance: {} degrees", options.angle_tolerance);
+
+    let mut converter = AnimationConverter::new(!options.no_filter, options.position_tolerance)
+        .with_angle_tolerance(options.angle_tolerance)
+        .with_filter_rest_pose_bones(options.filter_rest_bones)
+        .with_rest_pose(match &options.rest_pose_file {
+            Some(path) => RestPoseSource::from_file(path)?,
+            None => match options.rest_pose {
+                RestPoseArg::Node => RestPoseSource::Node,
+                RestPoseArg::Bind => RestPoseSource::BindPose,
+                RestPoseArg::FirstFrame => RestPoseSource::FirstKeyframe,
+            },
+        });
+    if !options.include_bones.is_empty() || !options.exclude_bones.is_empty() {
+        let mut selection = BoneSelection::new();
+        for pattern in &options.include_bones {
+            selection = selection.include(pattern)?;
+        }
+        for pattern in &options.exclude_bones {
+            selection = selection.exclude(pattern)?;
+        }
+        converter = converter.with_bone_selection(selection);
+    }
+    if let Some(path) = &options.bone_map {
+        converter = converter.with_bone_map(BoneMap::from_file(path)?);
+    }
+    if options.mirror {
+        let mut config = MirrorConfig {
+            plane: match options.mirror_axis {
+                AxisArg::X => MirrorPlane::X,
+                AxisArg::Y => MirrorPlane::Y,
+                AxisArg::Z => MirrorPlane::Z,
+            },
+            ..Default::default()
+        };
+        if !options.mirror_pairs.is_empty() {
+            config.name_pairs = options.mirror_pairs.clone();
+        }
+        converter = converter.with_mirror(config);
+    }
+    if let Some(preset) = options.preset {
+        let mut spec = match preset {
+            PresetArg::MixamoR15 => Preset::MixamoR15.spec(),
+            PresetArg::MixamoR6 => Preset::MixamoR6.spec(),
+        };
+        if let Some(scale) = options.preset_scale {
+            spec.position_scale = scale;
+        }
+        converter = converter.with_retarget(spec);
+    }
+    if options.smooth.is_some() || !options.smooth_bones.is_empty() || options.despike {
+        converter = converter.with_smoothing(SmoothingConfig {
+            strength: options.smooth.unwrap_or(0.0),
+            bone_strengths: options.smooth_bones.iter().cloned().collect(),
+            spike_tolerance: options.despike.then(|| {
+                Tolerance::new(
+                    options.despike_position_tolerance,
+                    options.despike_angle_tolerance,
+                )
+            }),
+        });
+    }
+    if options.in_place {
+        converter = converter.with_root_motion(RootMotionConfig {
+            bone: options.root_bone.clone(),
+            horizontal: true,
+            vertical: options.in_place_vertical,
+            yaw: options.in_place_yaw,
+        });
+    }
+    if options.additive {
+        let reference = match &options.additive_reference {
+            Some(path) => {
+                // Shape the reference like the clip, but keep every bone it poses
+                let keyframes = converter
+                    .clone()
+                    .with_filter_identical_bones(false)
+                    .with_filter_rest_pose_bones(false)
+                    .convert_file_to_keyframes(path)?;
+                let poses = additive::reference_pose(&keyframes, options.additive_frame);
+                if poses.is_empty() {
+                    bail!(
+                        "{} has no keyframe {} to use as the additive reference",
+                        path,
+                        options.additive_frame
+                    );
+                }
+                info!(
+                    "Using keyframe {} of {} as the additive reference",
+                    options.additive_frame, path
+                );
+                AdditiveReference::Poses(poses)
+            }
+            None => AdditiveReference::Frame(options.additive_frame),
+        };
+        converter = converter.with_additive(reference);
+    }
+    if options.reverse {
+        converter = converter.with_time_operation(TimeOperation::Reverse);
+    }
+    if options.ping_pong {
+        converter = converter.with_time_operation(TimeOperation::PingPong);
+    }
+    if let Some(factor) = options.time_scale {
+        converter = converter.with_time_operation(TimeOperation::Scale(factor));
+    }
+    if let Some(duration) = options.duration {
+        converter = converter.with_time_operation(TimeOperation::Stretch(duration));
+    }
+    if options.loop_mode {
+        converter = converter.with_loop_mode(LoopConfig {
+            tolerance: Tolerance::new(
+                options.loop_position_tolerance,
+                options.loop_angle_tolerance,
+            ),
+            seam: match options.loop_seam {
+                SeamArg::None => LoopSeam::None,
+                SeamArg::Match => LoopSeam::MatchStart,
+                SeamArg::Blend => LoopSeam::Blend {
+                    frames: options.loop_blend_frames,
+                },
+            },
+            force: options.force_loop,
+        });
+    }
+    if options.quantize {
+        converter = converter.with_quantization(Quantization::new(
+            options.quantize_position_step,
+            options.quantize_angle_step,
+        ));
+    }
+    if options.reduce {
+        converter = converter.with_keyframe_reduction(Tolerance::new(
+            options.reduce_position_tolerance,
+            options.reduce_angle_tolerance,
+        ));
+    }
+
+    let budget = Budget {
+        max_keyframes: options.max_keyframes,
+        max_poses: options.max_poses,
+        max_duration: options.max_duration,
+        max_size: options.max_size,
+        max_bones_per_keyframe: options.max_bones_per_keyframe,
+    };
+    if options.stats || budget != Budget::default() {
+        converter = converter.with_budget(budget);
+    }
+
+    Ok(converter)
+}
+
+/// Convert one input, optionally joined with more clips or split into several
+fn convert_single(args: &Args) -> Result<()> {
+    let input = args.input.as_deref().unwrap_or_default();
+    let output_file = args.output.clone().unwrap_or_else(|| {
         // Generate output filename from input
-        let path = Path::new(&args.input);
-        let stem = path.file_stem().unwrap().to_str().unwrap();
-        Box::leak(format!("{}.rbxm", stem).into_boxed_str())
+        let stem = Path::new(input).file_stem().unwrap().to_str().unwrap();
+        format!("{}.rbxm", stem)
     });
 
-    info!("Converting {} to {}", args.input, output_file);
-    debug!("Filter identical bones: {}", !args.no_filter);
-    debug!("Epsilon value: {}", args.epsilon);
+    if args.split.is_none() {
+        info!("Converting {} to {}", input, output_file);
+    }
 
-    // Configure the converter using the new API
-    let converter = AnimationConverter::new(!args.no_filter, args.epsilon);
+    let mut converter = build_converter(&args.options)?;
+    if let Some(name) = &args.animation {
+        converter = converter.with_animation(name);
+    }
 
     // Convert the file
-    let kfs = converter.convert_file_to_weakdom(&args.input)?;
+    let outputs = if let Some(ranges_path) = &args.split {
+        let ranges = ClipRanges::from_file(ranges_path)?;
+        info!(
+            "Splitting {} into {} clips in {}",
+            input,
+            ranges.ranges.len(),
+            args.split_dir
+        );
+        std::fs::create_dir_all(&args.split_dir)?;
+        converter
+            .convert_file_split(input, &ranges)?
+            .into_iter()
+            .map(|(name, kfs, report)| {
+                let output = Path::new(&args.split_dir).join(format!("{}.rbxm", name));
+                (output.display().to_string(), kfs, report)
+            })
+            .collect()
+    } else if args.append.is_empty() {
+        let (kfs, report) = converter.convert_file_with_report(input)?;
+        vec![(output_file.clone(), kfs, report)]
+    } else {
+        let mut clips = vec![ClipSource {
+            path: input.to_owned(),
+            animation: args.animation.clone(),
+        }];
+        clips.extend(args.append.iter().map(|spec| ClipSource::parse(spec)));
+        info!(
+            "Joining {} clips (gap {}s, crossfade {}s)",
+            clips.len(),
+            args.gap,
+            args.crossfade
+        );
+        let (kfs, report) = converter.convert_clips_with_report(
+            &clips,
+            &ConcatConfig {
+                gap: args.gap,
+                crossfade: args.crossfade,
+            },
+        )?;
+        vec![(output_file.clone(), kfs, report)]
+    };
+
+    let mut failed = Vec::new();
+    for (output_file, kfs, report) in outputs {
+        log_report(&args.options, &report);
 
-    // Write to output file
-    let output = BufWriter::new(File::create(output_file)?);
-    rbx_binary::to_writer(output, &kfs, &[kfs.root_ref()])?;
+        if let (Some(track), Some(path)) = (&report.root_motion, &args.root_motion_out) {
+            let is_json = Path::new(path)
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+            let contents = if is_json {
+                track.to_json()
+            } else {
+                track.to_luau()
+            };
+            std::fs::write(path, contents)?;
+            info!("Wrote root motion to {}", path);
+        }
 
-    info!("Successfully converted animation to {}", output_file);
+        if let Some(validation) = &report.validation
+            && !validation.passed()
+            && !args.options.budget_warn_only
+        {
+            warn!(
+                "{} exceeded {} budget limit(s); not written",
+                output_file,
+                validation.violations.len()
+            );
+            failed.push(output_file);
+            continue;
+        }
+
+        write_sequence(Path::new(&output_file), &kfs)?;
+
+        info!("Successfully converted animation to {}", output_file);
+    }
+
+    if !failed.is_empty() {
+        bail!("{} exceeded its budget: {}", input, failed.join(", "));
+    }
 
     Ok(())
 }
+
+/// Write a KeyframeSequence to an .rbxm file
+fn write_sequence(path: &Path, kfs: &WeakDom) -> Result<()> {
+    let output = BufWriter::new(
+        File::create(path).with_context(|| format!("Failed to create {}", path.display()))?,
+    );
+    rbx_binary::to_writer(output, kfs, &[kfs.root_ref()])?;
+    Ok(())
+}
+
+/// Log what each processing stage did
+fn log_report(options: &ConvertOptions, report: &ConversionReport) {
+    if let Some(smoothing) = &report.smoothing {
+        for spike in &smoothing.spikes {
+            debug!("  Spike on {} at {:.3}s", spike.bone, spike.time);
+        }
+        info!(
+            "Smoothed {} bone tracks, repaired {} spikes",
+            smoothing.smoothed_bones,
+            smoothing.spikes.len()
+        );
+    }
+
+    if let Some(track) = &report.root_motion {
+        let displacement = track.total_displacement();
+        info!(
+            "Extracted root motion from {} ({} samples, displacement {:.3}, {:.3}, {:.3})",
+            track.bone,
+            track.samples.len(),
+            displacement.x,
+            displacement.y,
+            displacement.z
+        );
+    } else if options.in_place {
+        warn!("No root bone found; root motion was not extracted");
+    }
+
+    if let Some(looping) = &report.looping {
+        info!(
+            "Loopability score {:.3} (max seam error {:.4} studs, {:.2} degrees{}), Loop = {}{}",
+            looping.score,
+            looping.max_position_error,
+            looping.max_angle_error,
+            looping
+                .worst_bone
+                .as_ref()
+                .map(|bone| format!(" at {}", bone))
+                .unwrap_or_default(),
+            looping.looped,
+            if looping.seam_fixed {
+                ", seam repaired"
+            } else {
+                ""
+            }
+        );
+    }
+
+    for removed in &report.removed_bones {
+        info!("Removed bone {} ({})", removed.name, removed.reason);
+    }
+
+    if let Some(reduction) = &report.reduction {
+        for bone in &reduction.bones {
+            debug!(
+                "  {}: {} -> {} poses",
+                bone.name, bone.poses_before, bone.poses_after
+            );
+        }
+        info!(
+            "Reduced {} -> {} poses ({:.1}% removed), {} -> {} keyframes",
+            reduction.poses_before(),
+            reduction.poses_after(),
+            reduction.removed_ratio() * 100.0,
+            reduction.keyframes_before,
+            reduction.keyframes_after
+        );
+    }
+
+    if let Some(validation) = &report.validation {
+        let stats = &validation.stats;
+        info!(
+            "Sequence: {} keyframes, {} poses, {:.3}s, {} bytes, {} max / {:.1} avg bones per keyframe",
+            stats.keyframe_count,
+            stats.pose_count,
+            stats.duration,
+            stats.serialized_size,
+            stats.max_bones_per_keyframe,
+            stats.average_bones_per_keyframe
+        );
+
+        for violation in &validation.violations {
+            warn!("Budget exceeded: {}", violation);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! The `watch` subcommand: reconverting inputs whenever they change
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::{debug, error, info, warn};
+
+use anim2rbx::batch::{BatchInput, OutputClaims, collect_inputs};
+use anim2rbx::watch::{Debouncer, snapshot};
+
+use crate::{WatchArgs, batch, build_converter};
+
+/// How often inputs are polled for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Convert every input once, then keep converting inputs that change until interrupted
+pub fn run(args: &WatchArgs) -> Result<()> {
+    let converter = build_converter(&args.batch.options)?;
+    let out_dir = Path::new(&args.batch.out_dir);
+    let claims = OutputClaims::default();
+
+    let inputs = scan(&args.batch.inputs);
+    check_clashes(args, out_dir, &inputs)?;
+    // Taken before converting, so files written during the first conversion are picked up
+    let initial = snapshot(inputs.keys().map(PathBuf::as_path));
+    if inputs.is_empty() {
+        warn!("No animation files found yet; waiting for some to appear");
+    } else {
+        let inputs: Vec<BatchInput> = inputs.values().cloned().collect();
+        let results = batch::convert_inputs(&converter, &inputs, &args.batch, out_dir, &claims);
+        let failed = results.iter().filter(|result| result.is_err()).count();
+        info!(
+            "Converted {} of {} files",
+            inputs.len() - failed,
+            inputs.len()
+        );
+    }
+
+    let mut debouncer = Debouncer::new(Duration::from_millis(args.debounce));
+    debouncer.prime(initial);
+    info!(
+        "Watching {} for changes (Ctrl+C to stop)",
+        args.batch.inputs.join(", ")
+    );
+
+    let mut clashing = false;
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let inputs = scan(&args.batch.inputs);
+        claims.retain(|path| inputs.contains_key(path));
+
+        // Changes wait, unreported, until the inputs no longer clash
+        match check_clashes(args, out_dir, &inputs) {
+            Err(err) if !clashing => {
+                error!("{:#}; waiting for the clash to be resolved", err);
+                clashing = true;
+                continue;
+            }
+            Err(_) => continue,
+            Ok(()) if clashing => {
+                info!("Output clash resolved");
+                clashing = false;
+            }
+            Ok(()) => {}
+        }
+
+        let changed = debouncer.update(
+            Instant::now(),
+            snapshot(inputs.keys().map(PathBuf::as_path)),
+        );
+
+        for path in changed {
+            let input = &inputs[&path];
+            let started = Instant::now();
+            match batch::convert_input(&converter, input, &args.batch, out_dir, &claims) {
+                Ok(outputs) => debug!(
+                    "{} converted to {} outputs in {:.0?}",
+                    path.display(),
+                    outputs.len(),
+                    started.elapsed()
+                ),
+                Err(err) => error!("{}: {:#}", path.display(), err),
+            }
+        }
+    }
+}
+
+/// Fail if two of the current inputs would be written to the same outputs
+fn check_clashes(
+    args: &WatchArgs,
+    out_dir: &Path,
+    inputs: &BTreeMap<PathBuf, BatchInput>,
+) -> Result<()> {
+    let inputs: Vec<BatchInput> = inputs.values().cloned().collect();
+    args.batch.name.check_clashes(out_dir, &inputs)
+}
+
+/// The current inputs by path; inputs that match nothing yet are skipped
+fn scan(specs: &[String]) -> BTreeMap<PathBuf, BatchInput> {
+    let mut inputs = BTreeMap::new();
+    for spec in specs {
+        match collect_inputs(std::slice::from_ref(spec)) {
+            Ok(found) => {
+                for input in found {
+                    inputs.entry(input.path.clone()).or_insert(input);
+                }
+            }
+            Err(err) => debug!("{:#}", err),
+        }
+    }
+    inputs
+}
@@ -0,0 +1,358 @@
+//! Combining clips on one timeline, and splitting long takes into clips
+//!
+//! Clips are placed one after another, each starting where the previous one ended plus
+//! an optional gap. A crossfade pulls the next clip earlier and blends it in with a slerp
+//! whose weight ramps linearly over the crossfade's length from the clip's start; any part
+//! of the crossfade past the previous clip's end blends from its held last pose.
+//!
+//! A long take is split with a ranges file, in TOML or JSON:
+//!
+//! ```toml
+//! fps = 30   # needed for frame ranges
+//!
+//! [[clips]]
+//! name = "Jab"
+//! start = 1.25
+//! end = 2.0
+//!
+//! [[clips]]
+//! name = "Kick"
+//! start_frame = 90
+//! end_frame = 140
+//! ```
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use glam::{Quat, Vec3};
+use ordered_float::OrderedFloat;
+use serde::Deserialize;
+
+use crate::types::{Keyframe, Pose};
+use crate::utils::{PoseTrack, cframe_from_glam, collect_pose_tracks, sample_pose_track};
+
+/// A clip to convert: a file, optionally narrowed to one of its animations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClipSource {
+    /// Path of the animation file
+    pub path: String,
+    /// Name of the animation in the file, or `None` for all of them
+    pub animation: Option<String>,
+}
+
+impl ClipSource {
+    /// Parse `FILE` or `FILE#ANIMATION`
+    ///
+    /// A path that exists as given is never split, so file names may contain `#`.
+    pub fn parse(spec: &str) -> Self {
+        match spec.rsplit_once('#') {
+            Some((path, animation)) if !Path::new(spec).exists() && !animation.is_empty() => Self {
+                path: path.to_owned(),
+                animation: Some(animation.to_owned()),
+            },
+            _ => Self {
+                path: spec.to_owned(),
+                animation: None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for ClipSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.animation {
+            Some(animation) => write!(f, "{}#{}", self.path, animation),
+            None => write!(f, "{}", self.path),
+        }
+    }
+}
+
+/// How neighbouring clips are joined
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConcatConfig {
+    /// Seconds of space between a clip's end and the next clip's start
+    pub gap: f64,
+    /// Seconds over which neighbouring clips overlap and blend, or `0` for a hard cut
+    pub crossfade: f64,
+}
+
+/// Join clips on one timeline
+///
+/// Every clip is shifted to start where the previous one ended, plus the gap, minus the
+/// crossfade, and blends in over the crossfade from its start. The crossfade is shortened
+/// to fit clips shorter than it. Bones a clip does not animate are held at their rest pose
+/// for that clip's span, so they don't drift between the clips that do animate them.
+pub fn concatenate(clips: &[Vec<Keyframe>], config: &ConcatConfig) -> Vec<Keyframe> {
+    let bones: BTreeSet<String> = clips
+        .iter()
+        .flatten()
+        .flat_map(|kf| kf.poses.iter().map(|pose| pose.name.clone()))
+        .collect();
+
+    let mut result: Vec<Keyframe> = Vec::new();
+    let mut previous_duration = 0.0;
+
+    for clip in clips.iter().filter(|clip| !clip.is_empty()) {
+        let mut clip = clip.clone();
+        clip.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let start = clip[0].time;
+        let duration = clip[clip.len() - 1].time - start;
+        pad_missing_bones(&mut clip, &bones);
+
+        let Some(end) = result.last().map(|kf| kf.time) else {
+            for keyframe in &mut clip {
+                keyframe.time -= start;
+            }
+            result = clip;
+            previous_duration = duration;
+            continue;
+        };
+
+        let crossfade = config
+            .crossfade
+            .min(duration)
+            .min(previous_duration + config.gap.max(0.0))
+            .max(0.0);
+        let offset = end + config.gap.max(0.0) - crossfade;
+        for keyframe in &mut clip {
+            keyframe.time += offset - start;
+        }
+
+        result = if crossfade > 0.0 {
+            crossfade_into(&result, &clip, offset, offset + crossfade)
+        } else {
+            cut_into(result, clip)
+        };
+        previous_duration = duration;
+    }
+
+    result
+}
+
+/// Give every bone a rest pose at the clip's first and last keyframe if it has no poses
+fn pad_missing_bones(clip: &mut [Keyframe], bones: &BTreeSet<String>) {
+    let posed: BTreeSet<&str> = clip
+        .iter()
+        .flat_map(|kf| kf.poses.iter().map(|pose| pose.name.as_str()))
+        .collect();
+    let missing: Vec<String> = bones
+        .iter()
+        .filter(|name| !posed.contains(name.as_str()))
+        .cloned()
+        .collect();
+
+    let last = clip.len() - 1;
+    for index in [0, last] {
+        for name in &missing {
+            if !clip[index].poses.iter().any(|pose| &pose.name == name) {
+                clip[index].poses.push(Pose::new(
+                    name.clone(),
+                    cframe_from_glam(Vec3::ZERO, Quat::IDENTITY),
+                ));
+            }
+        }
+    }
+}
+
+/// Append a clip, letting its poses replace the previous clip's at a shared time
+fn cut_into(mut result: Vec<Keyframe>, clip: Vec<Keyframe>) -> Vec<Keyframe> {
+    for keyframe in clip {
+        match result.last_mut() {
+            Some(last) if last.time >= keyframe.time => {
+                for pose in keyframe.poses {
+                    last.poses.retain(|existing| existing.name != pose.name);
+                    last.poses.push(pose);
+                }
+            }
+            _ => result.push(keyframe),
+        }
+    }
+    result
+}
+
+/// Blend the tail of `result` into the head of `clip` between `start` and `end`
+fn crossfade_into(result: &[Keyframe], clip: &[Keyframe], start: f64, end: f64) -> Vec<Keyframe> {
+    let outgoing = collect_pose_tracks(result);
+    let incoming = collect_pose_tracks(clip);
+    let bones: BTreeSet<&str> = outgoing.keys().chain(incoming.keys()).copied().collect();
+
+    let times: BTreeSet<OrderedFloat<f64>> = result
+        .iter()
+        .chain(clip)
+        .map(|kf| kf.time)
+        .filter(|&time| time >= start && time <= end)
+        .map(OrderedFloat)
+        .collect();
+
+    let blended = times.into_iter().map(|time| {
+        let time = time.into_inner();
+        let weight = ((time - start) / (end - start)) as f32;
+        let poses = bones
+            .iter()
+            .map(|&name| {
+                let sample = |tracks: &HashMap<&str, PoseTrack>| {
+                    tracks
+                        .get(name)
+                        .map(|track| sample_pose_track(track, time))
+                        .unwrap_or((Vec3::ZERO, Quat::IDENTITY))
+                };
+                let (from_position, from_rotation) = sample(&outgoing);
+                let (to_position, to_rotation) = sample(&incoming);
+                Pose::new(
+                    name,
+                    cframe_from_glam(
+                        from_position.lerp(to_position, weight),
+                        from_rotation.slerp(to_rotation, weight).normalize(),
+                    ),
+                )
+            })
+            .collect();
+        Keyframe { time, poses }
+    });
+
+    result
+        .iter()
+        .filter(|kf| kf.time < start)
+        .cloned()
+        .chain(blended)
+        .chain(clip.iter().filter(|kf| kf.time > end).cloned())
+        .collect()
+}
+
+/// On-disk layout of a ranges file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RangesFile {
+    #[serde(default)]
+    fps: Option<f64>,
+    clips: Vec<RangeFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RangeFile {
+    name: String,
+    start: Option<f64>,
+    end: Option<f64>,
+    start_frame: Option<f64>,
+    end_frame: Option<f64>,
+}
+
+/// A named part of a take, in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipRange {
+    /// Name of the clip, used for its output
+    pub name: String,
+    /// Start time in seconds
+    pub start: f64,
+    /// End time in seconds
+    pub end: f64,
+}
+
+/// The clips to split a take into, in file order
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClipRanges {
+    /// The clips, each cut from the take independently
+    pub ranges: Vec<ClipRange>,
+}
+
+impl ClipRanges {
+    /// Load a ranges file, choosing JSON or TOML by its extension
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read clip ranges {}", path.display()))?;
+
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let ranges = if is_json {
+            Self::from_json_str(&contents)
+        } else {
+            Self::from_toml_str(&contents)
+        };
+        ranges.with_context(|| format!("Invalid clip ranges {}", path.display()))
+    }
+
+    /// Parse ranges from TOML
+    pub fn from_toml_str(contents: &str) -> Result<Self> {
+        Self::from_ranges_file(toml::from_str(contents)?)
+    }
+
+    /// Parse ranges from JSON
+    pub fn from_json_str(contents: &str) -> Result<Self> {
+        Self::from_ranges_file(serde_json::from_str(contents)?)
+    }
+
+    fn from_ranges_file(file: RangesFile) -> Result<Self> {
+        let to_seconds = |seconds: Option<f64>, frame: Option<f64>, name: &str, bound: &str| match (
+            seconds, frame, file.fps,
+        ) {
+            (Some(seconds), None, _) => Ok(seconds),
+            (None, Some(frame), Some(fps)) if fps > 0.0 => Ok(frame / fps),
+            (None, Some(_), _) => bail!("Clip {} uses frames but no positive fps is set", name),
+            (Some(_), Some(_), _) => {
+                bail!("Clip {} sets both {} and {}_frame", name, bound, bound)
+            }
+            (None, None, _) => bail!("Clip {} has no {}", name, bound),
+        };
+
+        let mut ranges = Vec::new();
+        for clip in file.clips {
+            let start = to_seconds(clip.start, clip.start_frame, &clip.name, "start")?;
+            let end = to_seconds(clip.end, clip.end_frame, &clip.name, "end")?;
+            if end <= start {
+                bail!("Clip {} ends before it starts", clip.name);
+            }
+            if ranges
+                .iter()
+                .any(|range: &ClipRange| range.name == clip.name)
+            {
+                bail!("Clip {} is listed twice", clip.name);
+            }
+            ranges.push(ClipRange {
+                name: clip.name,
+                start,
+                end,
+            });
+        }
+        Ok(Self { ranges })
+    }
+}
+
+/// Cut the keyframes between `start` and `end` seconds out of a take
+///
+/// The slice starts at time zero. Its first and last keyframes pose every bone of the take
+/// at the range boundaries, interpolated from the poses around them.
+pub fn slice_keyframes(keyframes: &[Keyframe], start: f64, end: f64) -> Vec<Keyframe> {
+    let tracks = collect_pose_tracks(keyframes);
+    let mut bones: Vec<&str> = tracks.keys().copied().collect();
+    bones.sort_unstable();
+
+    let boundary = |time: f64| Keyframe {
+        time: time - start,
+        poses: bones
+            .iter()
+            .map(|&name| {
+                let (position, rotation) = sample_pose_track(&tracks[name], time);
+                Pose::new(name, cframe_from_glam(position, rotation))
+            })
+            .collect(),
+    };
+
+    let mut slice = vec![boundary(start)];
+    let mut inner: Vec<Keyframe> = keyframes
+        .iter()
+        .filter(|kf| kf.time > start && kf.time < end)
+        .cloned()
+        .collect();
+    inner.sort_by(|a, b| a.time.total_cmp(&b.time));
+    for keyframe in &mut inner {
+        keyframe.time -= start;
+    }
+    slice.extend(inner);
+    slice.push(boundary(end));
+    slice
+}
@@ -1,16 +1,23 @@
 //! Core conversion logic for transforming animation data
 
-use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use glam::{Mat3, Quat, Vec3};
-use log::debug;
+use glam::{Mat4, Quat, Vec3};
+use log::{debug, warn};
 use ordered_float::OrderedFloat;
 use rbx_dom_weak::{InstanceBuilder, WeakDom};
-use rbx_types::{CFrame, EnumItem, Matrix3, Ref, Variant, Vector3};
+use rbx_types::{CFrame, EnumItem, Ref, Variant};
+use russimp::Matrix4x4;
 use russimp::scene::Scene;
 
-use crate::types::{Keyframe, NodeInfo, Pose};
-use crate::utils::approx_equal_cframe;
+use crate::report::{BoneRemovalReason, RemovedBone};
+use crate::types::{Keyframe, NodeInfo, Pose, Tolerance};
+use crate::utils::{
+    cframe_from_glam, cframe_within_tolerance, decompose_matrix4x4, get_bone_infos_with_offsets,
+};
+
+/// Ticks per second assumed for animations that don't specify it
+pub const DEFAULT_TICKS_PER_SECOND: f64 = 24.0;
 
 /// Internal structure for efficiently looking up animation data
 struct ChannelData {
@@ -23,17 +30,46 @@ struct ChannelData {
 pub fn extract_keyframes_from_scene(
     scene: &Scene,
     node_infos: &HashMap<String, NodeInfo>,
+) -> Vec<Keyframe> {
+    let (_, offsets) = get_bone_infos_with_offsets(scene);
+    extract_animation_keyframes(scene, node_infos, &offsets, None)
+}
+
+/// Extract keyframes from the animation with the given name, or from every animation
+///
+/// Static nodes between bones are composed into `node_infos`, so keys get the same
+/// `offsets`, as returned by [`get_bone_infos_with_offsets`].
+pub fn extract_animation_keyframes(
+    scene: &Scene,
+    node_infos: &HashMap<String, NodeInfo>,
+    offsets: &HashMap<String, Mat4>,
+    animation: Option<&str>,
 ) -> Vec<Keyframe> {
     let mut keyframes = Vec::new();
     let mut channels_data = Vec::new();
     let mut all_times = BTreeSet::new();
 
     // Build efficient lookup structures for all channels
-    for anim in &scene.animations {
+    if let Some(name) = animation
+        && !scene.animations.iter().any(|anim| anim.name == name)
+    {
+        let names: Vec<&str> = scene
+            .animations
+            .iter()
+            .map(|anim| anim.name.as_str())
+            .collect();
+        warn!("Animation {} not found; available: {:?}", name, names);
+    }
+
+    for anim in scene
+        .animations
+        .iter()
+        .filter(|anim| animation.is_none_or(|name| anim.name == name))
+    {
         let ticks_per_second = if anim.ticks_per_second > 0.0 {
             anim.ticks_per_second
         } else {
-            24.0 // Default to 24 FPS if not specified
+            DEFAULT_TICKS_PER_SECOND
         };
 
         debug!("Animation: {} ticks per second", ticks_per_second);
@@ -84,89 +120,29 @@ pub fn extract_keyframes_from_scene(
                 continue;
             }
 
-            // Calculate position relative to rest pose
-            let pos = channel_data
-                .position_map
-                .get(&time_ordered)
-                .and_then(|value| {
-                    if let Some(node_info) = node_infos.get(&channel_data.name) {
-                        let rest_transform = node_info.rest_transform;
-                        let rest_pos = Vec3 {
-                            x: rest_transform.a4,
-                            y: rest_transform.b4,
-                            z: rest_transform.c4,
-                        };
-                        let relative_pos = Vec3 {
-                            x: value.x - rest_pos.x,
-                            y: value.y - rest_pos.y,
-                            z: value.z - rest_pos.z,
-                        };
-                        Some(relative_pos)
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or(Vec3::ZERO);
-
-            let rot = channel_data
-                .rotation_map
-                .get(&time_ordered)
-                .and_then(|value| {
-                    if let Some(node_info) = node_infos.get(&channel_data.name) {
-                        let rest_transform = node_info.rest_transform;
-                        let rest_rot = Quat::from_mat3(&Mat3::from_cols(
-                            Vec3 {
-                                x: rest_transform.a1,
-                                y: rest_transform.b1,
-                                z: rest_transform.c1,
-                            },
-                            Vec3 {
-                                x: rest_transform.a2,
-                                y: rest_transform.b2,
-                                z: rest_transform.c2,
-                            },
-                            Vec3 {
-                                x: rest_transform.a3,
-                                y: rest_transform.b3,
-                                z: rest_transform.c3,
-                            },
-                        ));
-                        let relative_rot = rest_rot.inverse()
-                            * Quat::from_xyzw(value.x, value.y, value.z, value.w);
-                        Some(relative_rot)
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or(Quat::IDENTITY);
+            let (pos, rot) = match node_infos.get(&channel_data.name) {
+                Some(node_info) => rest_relative_pose(
+                    &node_info.rest_transform,
+                    offsets
+                        .get(&channel_data.name)
+                        .copied()
+                        .unwrap_or(Mat4::IDENTITY),
+                    channel_data
+                        .position_map
+                        .get(&time_ordered)
+                        .map(|value| Vec3::new(value.x, value.y, value.z)),
+                    channel_data
+                        .rotation_map
+                        .get(&time_ordered)
+                        .map(|value| Quat::from_xyzw(value.x, value.y, value.z, value.w)),
+                ),
+                None => (Vec3::ZERO, Quat::IDENTITY),
+            };
 
             // Convert to CFrame
-            let from_glam = Mat3::from_quat(rot);
-            let cframe = CFrame::new(
-                Vector3::new(pos.x, pos.y, pos.z),
-                Matrix3 {
-                    x: Vector3 {
-                        x: from_glam.x_axis.x,
-                        y: from_glam.x_axis.y,
-                        z: from_glam.x_axis.z,
-                    },
-                    y: Vector3 {
-                        x: from_glam.y_axis.x,
-                        y: from_glam.y_axis.y,
-                        z: from_glam.y_axis.z,
-                    },
-                    z: Vector3 {
-                        x: from_glam.z_axis.x,
-                        y: from_glam.z_axis.y,
-                        z: from_glam.z_axis.z,
-                    },
-                },
-            );
-
-            poses.push(Pose {
-                name: channel_data.name.clone(),
-                cframe,
-            });
+            let cframe = cframe_from_glam(pos, rot);
+
+            poses.push(Pose::new(channel_data.name.clone(), cframe));
         }
 
         // Only add keyframe if it has poses
@@ -178,60 +154,129 @@ pub fn extract_keyframes_from_scene(
     keyframes
 }
 
-/// Filter out bones that have identical poses across all keyframes
-pub fn filter_identical_bone_poses(keyframes: &mut Vec<Keyframe>, epsilon: f32) {
-    debug!("Before filtering poses: {} keyframes", keyframes.len());
-    let mut total_poses_before = 0;
-    let mut total_poses_after = 0;
+/// Express an animated local transform relative to a bone's rest pose
+///
+/// `rest_transform` is the bone's rest in its recorded parent's space, and `offset` maps
+/// the bone's node-local parent space into that space. Missing keys stay at rest.
+pub fn rest_relative_pose(
+    rest_transform: &Matrix4x4,
+    offset: Mat4,
+    position: Option<Vec3>,
+    rotation: Option<Quat>,
+) -> (Vec3, Quat) {
+    let (rest_position, rest_rotation) = decompose_matrix4x4(rest_transform);
+    let (_, offset_rotation, _) = offset.to_scale_rotation_translation();
+
+    let relative_position = position
+        .map(|position| offset.transform_point3(position) - rest_position)
+        .unwrap_or(Vec3::ZERO);
+    let relative_rotation = rotation
+        .map(|rotation| rest_rotation.inverse() * offset_rotation * rotation)
+        .unwrap_or(Quat::IDENTITY);
+
+    (relative_position, relative_rotation)
+}
 
-    // First, collect all unique bone names
-    let mut all_bone_names = HashSet::new();
-    for keyframe in keyframes.iter() {
-        for pose in &keyframe.poses {
-            all_bone_names.insert(pose.name.clone());
-        }
-    }
+/// Filter out bones that have identical poses across all keyframes
+///
+/// Poses count as identical when they are within `tolerance` of the bone's first pose.
+/// Returns the removed bones, sorted by name.
+pub fn filter_identical_bone_poses(
+    keyframes: &mut Vec<Keyframe>,
+    tolerance: Tolerance,
+) -> Vec<RemovedBone> {
+    let mut bones_to_remove = BTreeSet::new();
 
     // Check each bone to see if it has identical poses across all keyframes
-    let mut bones_to_remove = HashSet::new();
-    for bone_name in &all_bone_names {
-        let mut bone_poses: Vec<&CFrame> = Vec::new();
-
-        // Collect all poses for this bone across all keyframes
-        for keyframe in keyframes.iter() {
-            if let Some(pose) = keyframe.poses.iter().find(|p| &p.name == bone_name) {
-                bone_poses.push(&pose.cframe);
-            }
-        }
-
-        // Check if all poses for this bone are identical
+    for (bone_name, bone_poses) in collect_bone_poses(keyframes) {
         if bone_poses.len() > 1 {
             let first_pose = bone_poses[0];
             let all_identical = bone_poses[1..]
                 .iter()
-                .all(|pose| approx_equal_cframe(first_pose, pose, epsilon));
+                .all(|pose| cframe_within_tolerance(first_pose, pose, tolerance));
 
             if all_identical {
-                bones_to_remove.insert(bone_name.clone());
                 debug!(
                     "Bone {} has identical poses across all keyframes, removing",
                     bone_name
                 );
+                bones_to_remove.insert(bone_name);
             }
         }
     }
 
-    // Remove poses for bones that have identical poses across all keyframes
+    remove_bones(keyframes, &bones_to_remove);
+
+    bones_to_remove
+        .into_iter()
+        .map(|name| RemovedBone {
+            name,
+            reason: BoneRemovalReason::IdenticalPoses,
+        })
+        .collect()
+}
+
+/// Filter out bones whose poses all stay within `tolerance` of the rest pose
+///
+/// Poses are relative to the rest pose, so a bone at rest has an identity CFrame.
+/// Unlike [`filter_identical_bone_poses`], bones keyed only once are also removed.
+/// Returns the removed bones, sorted by name.
+pub fn filter_rest_pose_bones(
+    keyframes: &mut Vec<Keyframe>,
+    tolerance: Tolerance,
+) -> Vec<RemovedBone> {
+    let identity = identity_cframe();
+    let mut bones_to_remove = BTreeSet::new();
+
+    for (bone_name, bone_poses) in collect_bone_poses(keyframes) {
+        let at_rest = bone_poses
+            .iter()
+            .all(|pose| cframe_within_tolerance(&identity, pose, tolerance));
+
+        if at_rest {
+            debug!("Bone {} stays at its rest pose, removing", bone_name);
+            bones_to_remove.insert(bone_name);
+        }
+    }
+
+    remove_bones(keyframes, &bones_to_remove);
+
+    bones_to_remove
+        .into_iter()
+        .map(|name| RemovedBone {
+            name,
+            reason: BoneRemovalReason::RestPose,
+        })
+        .collect()
+}
+
+/// Collect every bone's poses across all keyframes, in keyframe order
+fn collect_bone_poses(keyframes: &[Keyframe]) -> BTreeMap<String, Vec<&CFrame>> {
+    let mut bone_poses: BTreeMap<String, Vec<&CFrame>> = BTreeMap::new();
+    for keyframe in keyframes {
+        for pose in &keyframe.poses {
+            bone_poses
+                .entry(pose.name.clone())
+                .or_default()
+                .push(&pose.cframe);
+        }
+    }
+    bone_poses
+}
+
+/// Remove all poses of the given bones, then drop keyframes left without poses
+fn remove_bones(keyframes: &mut Vec<Keyframe>, bones_to_remove: &BTreeSet<String>) {
+    debug!("Before filtering poses: {} keyframes", keyframes.len());
+    let mut total_poses_before = 0;
+    let mut total_poses_after = 0;
+
     for keyframe in keyframes.iter_mut() {
         total_poses_before += keyframe.poses.len();
 
         keyframe.poses.retain(|pose| {
             let should_keep = !bones_to_remove.contains(&pose.name);
             if !should_keep {
-                debug!(
-                    "Removing identical pose: {} at time {}",
-                    pose.name, keyframe.time
-                );
+                debug!("Removing pose: {} at time {}", pose.name, keyframe.time);
             }
             should_keep
         });
@@ -254,6 +299,25 @@ pub fn filter_identical_bone_poses(keyframes: &mut Vec<Keyframe>, epsilon: f32)
 pub fn create_keyframe_sequence_dom(
     keyframes: &[Keyframe],
     bone_infos: &HashMap<String, NodeInfo>,
+) -> WeakDom {
+    build_keyframe_sequence_dom(keyframes, bone_infos, false)
+}
+
+/// Create a KeyframeSequence, filling in unposed ancestors with zero-weight placeholder poses
+///
+/// Target rigs such as R15 expect every pose at its place in the full hierarchy, even
+/// where an ancestor has no pose of its own at that time.
+pub fn create_keyframe_sequence_dom_with_placeholders(
+    keyframes: &[Keyframe],
+    bone_infos: &HashMap<String, NodeInfo>,
+) -> WeakDom {
+    build_keyframe_sequence_dom(keyframes, bone_infos, true)
+}
+
+fn build_keyframe_sequence_dom(
+    keyframes: &[Keyframe],
+    bone_infos: &HashMap<String, NodeInfo>,
+    placeholders: bool,
 ) -> WeakDom {
     // Create the WeakDom with KeyframeSequence and actual Keyframe instances
     let mut kfs = WeakDom::new(InstanceBuilder::new("KeyframeSequence").with_properties([(
@@ -275,50 +339,59 @@ pub fn create_keyframe_sequence_dom(
 
         // Create Pose instances with bone hierarchy
         let mut pose_refs: HashMap<String, Ref> = HashMap::new();
+        let mut pose_names: Vec<String> = Vec::new();
 
         // First, create all pose instances
         for pose in &keyframe.poses {
             debug!("  Creating pose for bone: {}", pose.name);
 
-            let pose_properties: Vec<(&str, Variant)> = vec![
-                ("CFrame", pose.cframe.clone().into()),
-                (
-                    "EasingDirection",
-                    EnumItem {
-                        ty: "EasingDirection".to_owned(),
-                        value: 0, // In
-                    }
-                    .into(),
-                ),
-                (
-                    "EasingStyle",
-                    EnumItem {
-                        ty: "EasingStyle".to_owned(),
-                        value: 0, // Linear
-                    }
-                    .into(),
-                ),
-            ];
-
-            let pose_instance = InstanceBuilder::new("Pose")
-                .with_name(pose.name.clone())
-                .with_properties(pose_properties);
+            let pose_instance = pose_builder(pose);
 
             // Temporarily insert under keyframe, we'll move them later
             let pose_ref = kfs.insert(keyframe_ref, pose_instance);
             pose_refs.insert(pose.name.clone(), pose_ref);
+            pose_names.push(pose.name.clone());
         }
 
-        // Now organize them by hierarchy
-        for pose in &keyframe.poses {
-            if let Some(bone_info) = bone_infos.get(&pose.name) {
-                if let Some(parent_name) = &bone_info.parent {
-                    // If parent exists in this keyframe's poses, move this pose under the parent
-                    if let (Some(&child_ref), Some(&parent_ref)) =
-                        (pose_refs.get(&pose.name), pose_refs.get(parent_name))
-                    {
-                        kfs.transfer_within(child_ref, parent_ref);
+        // Fill in ancestors without a pose at this time with zero-weight placeholders,
+        // so every pose sits at its place in the hierarchy
+        if placeholders {
+            for pose in &keyframe.poses {
+                let mut ancestor = bone_infos
+                    .get(&pose.name)
+                    .and_then(|bone_info| bone_info.parent.clone());
+
+                while let Some(ancestor_name) = ancestor {
+                    if pose_refs.contains_key(&ancestor_name) {
+                        break;
                     }
+
+                    debug!("  Creating placeholder pose for bone: {}", ancestor_name);
+                    let placeholder =
+                        pose_builder(&Pose::new(ancestor_name.clone(), identity_cframe()))
+                            .with_property("Weight", 0.0_f32);
+                    let pose_ref = kfs.insert(keyframe_ref, placeholder);
+                    pose_refs.insert(ancestor_name.clone(), pose_ref);
+                    pose_names.push(ancestor_name.clone());
+
+                    ancestor = bone_infos
+                        .get(&ancestor_name)
+                        .and_then(|bone_info| bone_info.parent.clone());
+                }
+            }
+        }
+
+        // Now organize them by hierarchy
+        for name in &pose_names {
+            if let Some(parent_name) = bone_infos
+                .get(name)
+                .and_then(|bone_info| bone_info.parent.as_ref())
+            {
+                // If parent exists in this keyframe's poses, move this pose under the parent
+                if let (Some(&child_ref), Some(&parent_ref)) =
+                    (pose_refs.get(name), pose_refs.get(parent_name))
+                {
+                    kfs.transfer_within(child_ref, parent_ref);
                 }
             }
         }
@@ -326,3 +399,42 @@ pub fn create_keyframe_sequence_dom(
 
     kfs
 }
+
+/// Build a Pose instance for a bone
+fn pose_builder(pose: &Pose) -> InstanceBuilder {
+    let pose_properties: Vec<(&str, Variant)> = vec![
+        ("CFrame", pose.cframe.into()),
+        (
+            "EasingDirection",
+            EnumItem {
+                ty: "EasingDirection".to_owned(),
+                value: pose.easing_direction.value(),
+            }
+            .into(),
+        ),
+        (
+            "EasingStyle",
+            EnumItem {
+                ty: "EasingStyle".to_owned(),
+                value: pose.easing_style.value(),
+            }
+            .into(),
+        ),
+    ];
+
+    InstanceBuilder::new("Pose")
+        .with_name(&pose.name)
+        .with_properties(pose_properties)
+}
+
+fn identity_cframe() -> CFrame {
+    cframe_from_glam(Vec3::ZERO, Quat::IDENTITY)
+}
+
+/// Set the `Loop` property of a KeyframeSequence created by [`create_keyframe_sequence_dom`]
+pub fn set_sequence_looped(kfs: &mut WeakDom, looped: bool) {
+    let root_ref = kfs.root_ref();
+    if let Some(root) = kfs.get_by_ref_mut(root_ref) {
+        root.properties.insert("Loop".into(), Variant::Bool(looped));
+    }
+}
@@ -0,0 +1,204 @@
+//! Forward kinematics: bone transforms in model and world space
+//!
+//! Keyframes hold each bone's pose relative to its rest transform in its parent's space.
+//! Composing rest and pose down the hierarchy gives every bone's transform in the scene
+//! (world space) and relative to the top-most bone of its skeleton (model space). Scale is
+//! not part of a pose, so transforms carry translation and rotation only.
+
+use std::collections::HashMap;
+
+use glam::{Quat, Vec3};
+use rbx_types::CFrame;
+
+use crate::sampling::SequenceSampler;
+use crate::types::{Keyframe, NodeInfo};
+use crate::utils::{cframe_from_glam, cframe_to_glam, decompose_matrix4x4};
+
+/// A translation and rotation, composed like a CFrame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    /// Translation applied after the rotation
+    pub translation: Vec3,
+    /// Rotation
+    pub rotation: Quat,
+}
+
+impl Transform {
+    /// The transform that leaves everything in place
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+    };
+
+    /// Create a transform from a translation and rotation
+    pub fn new(translation: Vec3, rotation: Quat) -> Self {
+        Self {
+            translation,
+            rotation,
+        }
+    }
+
+    /// The transform of a CFrame
+    pub fn from_cframe(cframe: &CFrame) -> Self {
+        let (translation, rotation) = cframe_to_glam(cframe);
+        Self::new(translation, rotation)
+    }
+
+    /// The CFrame of this transform
+    pub fn to_cframe(&self) -> CFrame {
+        cframe_from_glam(self.translation, self.rotation)
+    }
+
+    /// Map a point from this transform's space into its parent's
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.translation + self.rotation * point
+    }
+
+    /// Compose with a transform expressed in this one's space
+    pub fn mul(&self, child: &Transform) -> Transform {
+        Transform {
+            translation: self.translation + self.rotation * child.translation,
+            rotation: self.rotation * child.rotation,
+        }
+    }
+
+    /// The transform that undoes this one
+    pub fn inverse(&self) -> Transform {
+        let rotation = self.rotation.inverse();
+        Transform {
+            translation: rotation * -self.translation,
+            rotation,
+        }
+    }
+}
+
+/// Compose local transforms down the hierarchy
+///
+/// Bones whose parent is missing from `bone_infos` start from the identity.
+pub fn global_transforms<'a>(
+    bone_infos: &'a HashMap<String, NodeInfo>,
+    local: &dyn Fn(&str) -> Transform,
+) -> HashMap<&'a str, Transform> {
+    fn resolve<'a>(
+        name: &'a str,
+        bone_infos: &'a HashMap<String, NodeInfo>,
+        local: &dyn Fn(&str) -> Transform,
+        globals: &mut HashMap<&'a str, Transform>,
+    ) -> Transform {
+        if let Some(global) = globals.get(name) {
+            return *global;
+        }
+
+        let parent_global = bone_infos[name]
+            .parent
+            .as_deref()
+            .filter(|parent| bone_infos.contains_key(*parent))
+            .map(|parent| {
+                let (parent, _) = bone_infos.get_key_value(parent).unwrap();
+                resolve(parent, bone_infos, local, globals)
+            })
+            .unwrap_or(Transform::IDENTITY);
+
+        let global = parent_global.mul(&local(name));
+        globals.insert(name, global);
+        global
+    }
+
+    let mut globals = HashMap::new();
+    for name in bone_infos.keys() {
+        resolve(name, bone_infos, local, &mut globals);
+    }
+    globals
+}
+
+/// A bone's transform at one point in time, in each space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoneTransforms {
+    /// Rest composed with the pose, in the parent bone's space
+    pub local: Transform,
+    /// Relative to the top-most bone of the skeleton, so root motion does not show up
+    pub model: Transform,
+    /// In scene space, including the transforms of the nodes above the skeleton
+    pub world: Transform,
+}
+
+/// Evaluates a bone hierarchy posed by keyframes at arbitrary times
+pub struct FkEvaluator<'a> {
+    bone_infos: &'a HashMap<String, NodeInfo>,
+    rest: HashMap<&'a str, Transform>,
+    sampler: SequenceSampler,
+}
+
+impl<'a> FkEvaluator<'a> {
+    /// Prepare the hierarchy and the keyframes' per-bone tracks for evaluation
+    ///
+    /// Poses of bones missing from `bone_infos` are ignored.
+    pub fn new(bone_infos: &'a HashMap<String, NodeInfo>, keyframes: &[Keyframe]) -> Self {
+        let rest = bone_infos
+            .iter()
+            .map(|(name, info)| {
+                let (translation, rotation) = decompose_matrix4x4(&info.rest_transform);
+                (name.as_str(), Transform::new(translation, rotation))
+            })
+            .collect();
+        Self {
+            bone_infos,
+            rest,
+            sampler: SequenceSampler::new(keyframes),
+        }
+    }
+
+    /// Every bone's transforms at `time` seconds
+    ///
+    /// Bones are posed as Roblox's Animator would; see [`SequenceSampler`]. Bones without
+    /// poses stay at rest.
+    pub fn evaluate(&self, time: f64) -> HashMap<&'a str, BoneTransforms> {
+        let local = |name: &str| {
+            let rest = self.rest[name];
+            match self.sampler.sample_bone_glam(name, time) {
+                Some((position, rotation)) => {
+                    Transform::new(rest.translation + position, rest.rotation * rotation)
+                }
+                None => rest,
+            }
+        };
+        let worlds = global_transforms(self.bone_infos, &local);
+
+        worlds
+            .iter()
+            .map(|(&name, &world)| {
+                let root = worlds[self.root_of(name)];
+                (
+                    name,
+                    BoneTransforms {
+                        local: local(name),
+                        model: root.inverse().mul(&world),
+                        world,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// The top-most ancestor of a bone within the hierarchy
+    fn root_of(&self, name: &'a str) -> &'a str {
+        let mut current = name;
+        while let Some((parent, _)) = self.bone_infos[current]
+            .parent
+            .as_ref()
+            .and_then(|parent| self.bone_infos.get_key_value(parent))
+        {
+            current = parent;
+        }
+        current
+    }
+}
+
+/// Every bone's transforms at `time` seconds; see [`FkEvaluator::evaluate`]
+pub fn evaluate<'a>(
+    bone_infos: &'a HashMap<String, NodeInfo>,
+    keyframes: &[Keyframe],
+    time: f64,
+) -> HashMap<&'a str, BoneTransforms> {
+    FkEvaluator::new(bone_infos, keyframes).evaluate(time)
+}
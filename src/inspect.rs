@@ -0,0 +1,295 @@
+//! Summaries of source animation files: clips, node hierarchy, channels and problems
+//!
+//! An inspection describes the file as Assimp loaded it, before any conversion stage runs,
+//! so FBX pivot helpers and nodes are listed under their original names. Problems are
+//! things that make the converted sequence differ from what the file suggests.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use anyhow::{Context, Result};
+use russimp::node::Node;
+use russimp::scene::Scene;
+use serde::Serialize;
+
+use crate::converter::DEFAULT_TICKS_PER_SECOND;
+use crate::utils::fbx_helper_owner;
+
+/// Scale keys further than this from the channel's first one count as animated scale
+const SCALE_EPSILON: f32 = 1e-4;
+
+/// Everything an inspection found in a file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Inspection {
+    /// The animations, in file order
+    pub clips: Vec<ClipInfo>,
+    /// The node hierarchy, or `None` if the file has no nodes
+    pub root: Option<InspectedNode>,
+    /// Detected problems, in the order they were found
+    pub problems: Vec<Problem>,
+}
+
+/// One animation in a file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClipInfo {
+    /// Name of the animation, as used by `--animation`
+    pub name: String,
+    /// Length in seconds
+    pub duration: f64,
+    /// Length in ticks, as stored
+    pub duration_ticks: f64,
+    /// Ticks per second as stored, or `0` if the file leaves it unset
+    pub ticks_per_second: f64,
+    /// The animation's channels, in file order
+    pub channels: Vec<ChannelInfo>,
+}
+
+/// Key counts of one node's channel in an animation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChannelInfo {
+    /// Name of the animated node
+    pub node: String,
+    /// Number of position keys
+    pub position_keys: usize,
+    /// Number of rotation keys
+    pub rotation_keys: usize,
+    /// Number of scaling keys
+    pub scaling_keys: usize,
+    /// Whether the scaling keys change, which poses can't represent
+    pub scale_animated: bool,
+}
+
+/// A node of the hierarchy
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InspectedNode {
+    /// Name of the node
+    pub name: String,
+    /// Whether any animation has a channel for this node
+    pub animated: bool,
+    /// Whether a mesh is skinned to this node
+    pub bone: bool,
+    /// Child nodes, in file order
+    pub children: Vec<InspectedNode>,
+}
+
+impl InspectedNode {
+    /// This node and its descendants, depth first, with their depth below this node
+    pub fn walk(&self) -> Vec<(usize, &InspectedNode)> {
+        fn visit<'a>(
+            node: &'a InspectedNode,
+            depth: usize,
+            nodes: &mut Vec<(usize, &'a InspectedNode)>,
+        ) {
+            nodes.push((depth, node));
+            for child in &node.children {
+                visit(child, depth + 1, nodes);
+            }
+        }
+
+        let mut nodes = Vec::new();
+        visit(self, 0, &mut nodes);
+        nodes
+    }
+}
+
+/// Something in a file that conversion will not carry over as the file suggests
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Problem {
+    /// The file has no animations
+    NoAnimations,
+    /// An animation has no keys at all
+    EmptyAnimation { animation: String },
+    /// Several animations share a name, so they can't be selected on their own
+    DuplicateAnimation { animation: String },
+    /// An animation doesn't set its tick rate, so the default is assumed
+    UnsetTicksPerSecond { animation: String },
+    /// An FBX pivot helper node, folded into its owning bone on conversion
+    HelperNode { node: String, owner: String },
+    /// A channel animates a node missing from the hierarchy, so it has no rest transform
+    MissingNode { animation: String, node: String },
+    /// A channel animates scale, which poses don't carry
+    AnimatedScale { animation: String, node: String },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAnimations => write!(f, "the file has no animations"),
+            Self::EmptyAnimation { animation } => {
+                write!(f, "animation {} has no keys", animation)
+            }
+            Self::DuplicateAnimation { animation } => write!(
+                f,
+                "several animations are named {}; they can only be converted together",
+                animation
+            ),
+            Self::UnsetTicksPerSecond { animation } => write!(
+                f,
+                "animation {} doesn't set ticks per second; assuming {}",
+                animation, DEFAULT_TICKS_PER_SECOND
+            ),
+            Self::HelperNode { node, owner } => {
+                write!(f, "helper node {} is folded into {}", node, owner)
+            }
+            Self::MissingNode { animation, node } => write!(
+                f,
+                "animation {} animates {}, which is not in the hierarchy",
+                animation, node
+            ),
+            Self::AnimatedScale { animation, node } => write!(
+                f,
+                "animation {} scales {}; scale is dropped on conversion",
+                animation, node
+            ),
+        }
+    }
+}
+
+impl Inspection {
+    /// Build an inspection from clips and a hierarchy, detecting problems
+    pub fn new(clips: Vec<ClipInfo>, root: Option<InspectedNode>) -> Self {
+        let mut problems = Vec::new();
+        if clips.is_empty() {
+            problems.push(Problem::NoAnimations);
+        }
+
+        let mut seen = HashSet::new();
+        let mut reported = HashSet::new();
+        for clip in &clips {
+            if !seen.insert(clip.name.as_str()) && reported.insert(clip.name.as_str()) {
+                problems.push(Problem::DuplicateAnimation {
+                    animation: clip.name.clone(),
+                });
+            }
+            if clip.ticks_per_second <= 0.0 {
+                problems.push(Problem::UnsetTicksPerSecond {
+                    animation: clip.name.clone(),
+                });
+            }
+            let keys: usize = clip
+                .channels
+                .iter()
+                .map(|channel| channel.position_keys + channel.rotation_keys + channel.scaling_keys)
+                .sum();
+            if keys == 0 {
+                problems.push(Problem::EmptyAnimation {
+                    animation: clip.name.clone(),
+                });
+            }
+        }
+
+        let nodes: Vec<&InspectedNode> = root
+            .iter()
+            .flat_map(|root| root.walk())
+            .map(|(_, node)| node)
+            .collect();
+        for node in &nodes {
+            if let Some(owner) = fbx_helper_owner(&node.name) {
+                problems.push(Problem::HelperNode {
+                    node: node.name.clone(),
+                    owner: owner.to_owned(),
+                });
+            }
+        }
+
+        let names: HashSet<&str> = nodes.iter().map(|node| node.name.as_str()).collect();
+        for clip in &clips {
+            for channel in &clip.channels {
+                if !names.contains(channel.node.as_str()) {
+                    problems.push(Problem::MissingNode {
+                        animation: clip.name.clone(),
+                        node: channel.node.clone(),
+                    });
+                }
+                if channel.scale_animated {
+                    problems.push(Problem::AnimatedScale {
+                        animation: clip.name.clone(),
+                        node: channel.node.clone(),
+                    });
+                }
+            }
+        }
+
+        Self {
+            clips,
+            root,
+            problems,
+        }
+    }
+}
+
+/// Inspect an animation file
+pub fn inspect_file(file_path: &str) -> Result<Inspection> {
+    let scene = Scene::from_file(file_path, vec![])
+        .with_context(|| format!("Failed to load {}", file_path))?;
+    Ok(inspect_scene(&scene))
+}
+
+/// Inspect an Assimp scene
+pub fn inspect_scene(scene: &Scene) -> Inspection {
+    let clips: Vec<ClipInfo> = scene
+        .animations
+        .iter()
+        .map(|animation| {
+            let ticks_per_second = if animation.ticks_per_second > 0.0 {
+                animation.ticks_per_second
+            } else {
+                DEFAULT_TICKS_PER_SECOND
+            };
+            ClipInfo {
+                name: animation.name.clone(),
+                duration: animation.duration / ticks_per_second,
+                duration_ticks: animation.duration,
+                ticks_per_second: animation.ticks_per_second,
+                channels: animation
+                    .channels
+                    .iter()
+                    .map(|channel| ChannelInfo {
+                        node: channel.name.clone(),
+                        position_keys: channel.position_keys.len(),
+                        rotation_keys: channel.rotation_keys.len(),
+                        scaling_keys: channel.scaling_keys.len(),
+                        scale_animated: channel.scaling_keys.first().is_some_and(|first| {
+                            channel.scaling_keys.iter().any(|key| {
+                                (key.value.x - first.value.x).abs() > SCALE_EPSILON
+                                    || (key.value.y - first.value.y).abs() > SCALE_EPSILON
+                                    || (key.value.z - first.value.z).abs() > SCALE_EPSILON
+                            })
+                        }),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let animated: HashSet<&str> = clips
+        .iter()
+        .flat_map(|clip| clip.channels.iter().map(|channel| channel.node.as_str()))
+        .collect();
+    let bones: HashSet<&str> = scene
+        .meshes
+        .iter()
+        .flat_map(|mesh| mesh.bones.iter().map(|bone| bone.name.as_str()))
+        .collect();
+
+    fn build(node: &Node, animated: &HashSet<&str>, bones: &HashSet<&str>) -> InspectedNode {
+        InspectedNode {
+            name: node.name.clone(),
+            animated: animated.contains(node.name.as_str()),
+            bone: bones.contains(node.name.as_str()),
+            children: node
+                .children
+                .borrow()
+                .iter()
+                .map(|child| build(child, animated, bones))
+                .collect(),
+        }
+    }
+
+    let root = scene
+        .root
+        .as_ref()
+        .map(|root| build(root, &animated, &bones));
+    Inspection::new(clips, root)
+}
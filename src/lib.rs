@@ -4,36 +4,125 @@
 //!
 //! This library provides functionality to:
 //! - Parse animation files using Assimp
-//! - Extract bone hierarchies and keyframe data
+//! - Inspect source files for clips, hierarchy, channel key counts and problems
+//! - Extract bone hierarchies and keyframe data, folding FBX pivot helpers into their bones
+//! - Sample keyframes at any time with Roblox's per-bone easing and interpolation
+//! - Evaluate model- and world-space bone transforms at any time
+//! - Take rest poses from the node graph, the mesh bind pose, the first frame or another file
+//! - Select bone subtrees or drop single bones by glob or regex
+//! - Rename or drop bones through mapping files
+//! - Mirror animations left/right
+//! - Retarget Mixamo skeletons onto the R15 and R6 rigs
+//! - Smooth capture noise and repair single-frame spikes
+//! - Extract root motion into a separate track for in-place clips
+//! - Turn clips into additive animations against a reference pose
+//! - Scale, stretch, reverse and ping-pong clips in time
+//! - Concatenate clips on one timeline with gaps or crossfades, or split long takes into clips
 //! - Convert to Roblox-compatible KeyframeSequence format
+//! - Find the inputs of batch conversions and name their outputs from templates
+//! - Detect settled changes to watched input files
+//! - Validate sequences against size and keyframe budgets
 //! - Filter and optimize animation data
+//! - Detect looping clips and repair their seams
+//! - Quantize pose values and reduce keyframes within physical error bounds
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
+use glam::Mat4;
+use log::warn;
 use rbx_dom_weak::WeakDom;
 use russimp::scene::Scene;
 
+pub mod additive;
+pub mod batch;
+pub mod clips;
 pub mod converter;
+pub mod fk;
+pub mod inspect;
+pub mod looping;
+pub mod mirror;
+pub mod optimize;
+pub mod remap;
+pub mod report;
+pub mod rest_pose;
+pub mod retarget;
+pub mod root_motion;
+pub mod sampling;
+pub mod selection;
+pub mod smoothing;
+pub mod timing;
 pub mod types;
 pub mod utils;
+pub mod validation;
+pub mod watch;
 
 pub use converter::*;
+pub use report::*;
 pub use types::*;
 
 /// Main library API for converting animation files to KeyframeSequence
+#[derive(Clone)]
 pub struct AnimationConverter {
     /// Whether to filter out bones with identical poses across all keyframes
     pub filter_identical_bones: bool,
-    /// Epsilon value for floating-point comparisons
+    /// Whether to filter out bones whose poses all stay at the rest pose
+    pub filter_rest_pose_bones: bool,
+    /// Translation tolerance in studs for pose comparisons
     pub epsilon: f32,
+    /// Rotation tolerance in degrees for pose comparisons
+    pub angle_tolerance: f32,
+    /// Name of the animation to convert, or `None` to merge every animation in the file
+    pub animation: Option<String>,
+    /// Where bone rest transforms come from; poses are expressed relative to them
+    pub rest_pose: rest_pose::RestPoseSource,
+    /// Include/exclude patterns applied right after extraction, or `None` to keep every bone
+    pub bone_selection: Option<selection::BoneSelection>,
+    /// Bone renaming applied right after bone selection, or `None` to keep source names
+    pub bone_map: Option<remap::BoneMap>,
+    /// Left/right mirroring applied after bone mapping, or `None` to keep the clip's handedness
+    pub mirror: Option<mirror::MirrorConfig>,
+    /// Target rig to retarget onto after bone mapping, or `None` to keep the source skeleton
+    pub retarget: Option<retarget::RetargetSpec>,
+    /// Noise smoothing and spike repair after retargeting, or `None` to keep tracks as captured
+    pub smoothing: Option<smoothing::SmoothingConfig>,
+    /// Root motion to strip from the root bone, or `None` to keep the clip as authored
+    pub root_motion: Option<root_motion::RootMotionConfig>,
+    /// Reference pose to subtract from every pose, or `None` to keep absolute poses
+    pub additive: Option<additive::AdditiveReference>,
+    /// Time operations applied in order after the pose stages
+    pub time_operations: Vec<timing::TimeOperation>,
+    /// Loop detection and seam settings, or `None` to leave the `Loop` property unset
+    pub loop_mode: Option<looping::LoopConfig>,
+    /// Precision to round pose values to, or `None` to keep full precision
+    pub quantization: Option<optimize::Quantization>,
+    /// Error bounds for keyframe reduction, or `None` to keep every pose
+    pub keyframe_reduction: Option<Tolerance>,
+    /// Budget to validate the output sequence against, or `None` to skip validation
+    pub budget: Option<validation::Budget>,
 }
 
 impl Default for AnimationConverter {
     fn default() -> Self {
         Self {
             filter_identical_bones: true,
+            filter_rest_pose_bones: false,
             epsilon: 1e-5,
+            angle_tolerance: 1e-3,
+            animation: None,
+            rest_pose: rest_pose::RestPoseSource::Node,
+            bone_selection: None,
+            bone_map: None,
+            mirror: None,
+            retarget: None,
+            smoothing: None,
+            root_motion: None,
+            additive: None,
+            time_operations: Vec::new(),
+            loop_mode: None,
+            quantization: None,
+            keyframe_reduction: None,
+            budget: None,
         }
     }
 }
@@ -44,6 +133,7 @@ impl AnimationConverter {
         Self {
             filter_identical_bones,
             epsilon,
+            ..Default::default()
         }
     }
 
@@ -53,12 +143,113 @@ impl AnimationConverter {
         self
     }
 
-    /// Builder method to set the epsilon value for floating-point comparisons
+    /// Builder method to set whether to filter bones that stay at their rest pose
+    pub fn with_filter_rest_pose_bones(mut self, enabled: bool) -> Self {
+        self.filter_rest_pose_bones = enabled;
+        self
+    }
+
+    /// Builder method to set the translation tolerance in studs for pose comparisons
     pub fn with_epsilon(mut self, epsilon: f32) -> Self {
         self.epsilon = epsilon;
         self
     }
 
+    /// Builder method to set the rotation tolerance in degrees for pose comparisons
+    pub fn with_angle_tolerance(mut self, angle_tolerance: f32) -> Self {
+        self.angle_tolerance = angle_tolerance;
+        self
+    }
+
+    /// The tolerance used when comparing poses
+    pub fn comparison_tolerance(&self) -> Tolerance {
+        Tolerance::new(self.epsilon, self.angle_tolerance)
+    }
+
+    /// Builder method to convert only the animation with this name
+    pub fn with_animation(mut self, name: impl Into<String>) -> Self {
+        self.animation = Some(name.into());
+        self
+    }
+
+    /// Builder method to choose where bone rest transforms come from
+    pub fn with_rest_pose(mut self, source: rest_pose::RestPoseSource) -> Self {
+        self.rest_pose = source;
+        self
+    }
+
+    /// Builder method to keep only the bones selected by include/exclude patterns
+    pub fn with_bone_selection(mut self, selection: selection::BoneSelection) -> Self {
+        self.bone_selection = Some(selection);
+        self
+    }
+
+    /// Builder method to rename bones with a bone map
+    pub fn with_bone_map(mut self, bone_map: remap::BoneMap) -> Self {
+        self.bone_map = Some(bone_map);
+        self
+    }
+
+    /// Builder method to mirror the animation across a plane, swapping left and right bones
+    pub fn with_mirror(mut self, config: mirror::MirrorConfig) -> Self {
+        self.mirror = Some(config);
+        self
+    }
+
+    /// Builder method to retarget the animation onto another rig
+    pub fn with_retarget(mut self, spec: retarget::RetargetSpec) -> Self {
+        self.retarget = Some(spec);
+        self
+    }
+
+    /// Builder method to smooth bone tracks and repair spikes
+    pub fn with_smoothing(mut self, config: smoothing::SmoothingConfig) -> Self {
+        self.smoothing = Some(config);
+        self
+    }
+
+    /// Builder method to extract root motion and make the clip play in place
+    pub fn with_root_motion(mut self, config: root_motion::RootMotionConfig) -> Self {
+        self.root_motion = Some(config);
+        self
+    }
+
+    /// Builder method to make the clip additive against a reference pose
+    pub fn with_additive(mut self, reference: additive::AdditiveReference) -> Self {
+        self.additive = Some(reference);
+        self
+    }
+
+    /// Builder method to append a time operation such as scaling or reversing
+    pub fn with_time_operation(mut self, operation: timing::TimeOperation) -> Self {
+        self.time_operations.push(operation);
+        self
+    }
+
+    /// Builder method to enable loop detection and seam preparation
+    pub fn with_loop_mode(mut self, config: looping::LoopConfig) -> Self {
+        self.loop_mode = Some(config);
+        self
+    }
+
+    /// Builder method to enable quantization of pose values
+    pub fn with_quantization(mut self, quantization: optimize::Quantization) -> Self {
+        self.quantization = Some(quantization);
+        self
+    }
+
+    /// Builder method to enable error-bounded keyframe reduction
+    pub fn with_keyframe_reduction(mut self, tolerance: Tolerance) -> Self {
+        self.keyframe_reduction = Some(tolerance);
+        self
+    }
+
+    /// Builder method to measure the output sequence and check it against a budget
+    pub fn with_budget(mut self, budget: validation::Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
     /// Convert an animation file to keyframes
     pub fn convert_file_to_keyframes(&self, file_path: &str) -> Result<Vec<Keyframe>> {
         let scene = Scene::from_file(file_path, vec![])?;
@@ -67,42 +258,340 @@ impl AnimationConverter {
 
     /// Convert an Assimp Scene to keyframes
     pub fn convert_scene_to_keyframes(&self, scene: &Scene) -> Vec<Keyframe> {
-        let bone_infos = utils::get_bone_infos(scene);
-        let mut keyframes = self.extract_keyframes(scene, &bone_infos);
+        self.convert_scene_with_report(scene).0
+    }
+
+    /// Convert an Assimp Scene to keyframes, reporting what each processing stage did
+    pub fn convert_scene_with_report(&self, scene: &Scene) -> (Vec<Keyframe>, ConversionReport) {
+        let (mut keyframes, mut report) = self.prepare_scene_keyframes(scene);
+        self.finish_keyframes(&mut keyframes, &mut report);
+        (keyframes, report)
+    }
+
+    /// Run the per-clip stages on a scene, from extraction up to the time operations
+    ///
+    /// Clips prepared this way can be combined before [`Self::finish_keyframes`] runs.
+    pub fn prepare_scene_keyframes(&self, scene: &Scene) -> (Vec<Keyframe>, ConversionReport) {
+        let (mut keyframes, report) = self.prepare_source_keyframes(scene);
+        self.apply_time_operations(&mut keyframes);
+        (keyframes, report)
+    }
+
+    /// Run the per-clip stages that work on source time, i.e. all but the time operations
+    fn prepare_source_keyframes(&self, scene: &Scene) -> (Vec<Keyframe>, ConversionReport) {
+        let (node_infos, offsets) = self.source_bone_infos(scene);
+        let mut keyframes = self.extract_keyframes(scene, &node_infos, &offsets);
+        let mut report = ConversionReport::default();
+
+        // Fold FBX pivot helpers first so every later stage sees the real bone names
+        utils::fold_fbx_helpers(&mut keyframes, &node_infos);
+        let bone_infos = utils::fold_fbx_helper_infos(&node_infos);
+
+        if let Some(selection) = &self.bone_selection {
+            report.removed_bones.extend(
+                selection
+                    .apply_to_keyframes(&mut keyframes, &bone_infos)
+                    .into_iter()
+                    .map(|name| RemovedBone {
+                        name,
+                        reason: BoneRemovalReason::Excluded,
+                    }),
+            );
+        }
+
+        if let Some(bone_map) = &self.bone_map {
+            report
+                .removed_bones
+                .extend(bone_map.apply_to_keyframes(&mut keyframes, &bone_infos));
+        }
+
+        // Mirror in source space, where the full hierarchy and its rest poses are known
+        if let Some(config) = &self.mirror {
+            mirror::mirror_keyframes(&mut keyframes, &self.mapped_bone_infos(scene), config);
+        }
+
+        if let Some(spec) = &self.retarget {
+            keyframes =
+                retarget::retarget_keyframes(&keyframes, &self.mapped_bone_infos(scene), spec);
+        }
+
+        // Smooth in output space, where spike tolerances are in studs, and before root
+        // motion so the extracted track is free of capture noise
+        if let Some(config) = &self.smoothing {
+            report.smoothing = Some(smoothing::smooth_keyframes(&mut keyframes, config));
+        }
+
+        // Strip root motion before the loop seam is measured, as in-place clips loop better
+        if let Some(config) = &self.root_motion {
+            report.root_motion = root_motion::extract_root_motion(
+                &mut keyframes,
+                &self.output_bone_infos(scene),
+                config,
+            );
+        }
+
+        if let Some(reference) = &self.additive {
+            additive::make_additive(&mut keyframes, reference);
+        }
+
+        (keyframes, report)
+    }
+
+    fn apply_time_operations(&self, keyframes: &mut Vec<Keyframe>) {
+        for operation in &self.time_operations {
+            operation.apply(keyframes);
+        }
+    }
+
+    /// Run the whole-sequence stages: loop handling, quantization, filtering and reduction
+    pub fn finish_keyframes(&self, keyframes: &mut Vec<Keyframe>, report: &mut ConversionReport) {
+        // Fix the loop seam first so later passes see the final end poses
+        if let Some(config) = self.loop_mode {
+            report.looping = Some(looping::prepare_loop(keyframes, config));
+        }
+
+        // Quantize before filtering so noise-level differences no longer keep bones alive
+        if let Some(quantization) = self.quantization {
+            optimize::quantize_keyframes(keyframes, quantization);
+        }
+
+        // Rest pose filtering runs first so bones keyed only at rest report the more specific rule
+        if self.filter_rest_pose_bones {
+            report
+                .removed_bones
+                .extend(converter::filter_rest_pose_bones(
+                    keyframes,
+                    self.comparison_tolerance(),
+                ));
+        }
 
         if self.filter_identical_bones {
-            self.filter_identical_poses(&mut keyframes);
+            report
+                .removed_bones
+                .extend(self.filter_identical_poses(keyframes));
         }
 
-        keyframes
+        if let Some(tolerance) = self.keyframe_reduction {
+            report.reduction = Some(optimize::reduce_keyframes(keyframes, tolerance));
+        }
     }
 
     /// Convert keyframes to a Roblox WeakDom KeyframeSequence
+    ///
+    /// Retargeted sequences get placeholder poses for unposed ancestors; see
+    /// [`converter::create_keyframe_sequence_dom_with_placeholders`].
     pub fn keyframes_to_weakdom(
         &self,
         keyframes: &[Keyframe],
         bone_infos: &HashMap<String, NodeInfo>,
     ) -> WeakDom {
-        converter::create_keyframe_sequence_dom(keyframes, bone_infos)
+        if self.retarget.is_some() {
+            converter::create_keyframe_sequence_dom_with_placeholders(keyframes, bone_infos)
+        } else {
+            converter::create_keyframe_sequence_dom(keyframes, bone_infos)
+        }
     }
 
     /// Convert an animation file directly to a Roblox WeakDom KeyframeSequence
     pub fn convert_file_to_weakdom(&self, file_path: &str) -> Result<WeakDom> {
+        Ok(self.convert_file_with_report(file_path)?.0)
+    }
+
+    /// Convert an animation file to a Roblox WeakDom KeyframeSequence along with a conversion report
+    pub fn convert_file_with_report(&self, file_path: &str) -> Result<(WeakDom, ConversionReport)> {
         let scene = Scene::from_file(file_path, vec![])?;
-        let bone_infos = utils::get_bone_infos(&scene);
-        let keyframes = self.convert_scene_to_keyframes(&scene);
-        Ok(self.keyframes_to_weakdom(&keyframes, &bone_infos))
+        let bone_infos = self.output_bone_infos(&scene);
+        let (keyframes, report) = self.convert_scene_with_report(&scene);
+        self.build_sequence(&keyframes, &bone_infos, report)
+    }
+
+    /// Convert several clips and join them into one KeyframeSequence
+    ///
+    /// Each clip runs through the per-clip stages on its own, then the joined timeline runs
+    /// through the whole-sequence stages. The report keeps the first clip's root motion.
+    pub fn convert_clips_with_report(
+        &self,
+        clips: &[clips::ClipSource],
+        config: &clips::ConcatConfig,
+    ) -> Result<(WeakDom, ConversionReport)> {
+        let mut bone_infos = HashMap::new();
+        let mut sequences = Vec::new();
+        let mut report = ConversionReport::default();
+
+        for clip in clips {
+            let scene = Scene::from_file(&clip.path, vec![])
+                .with_context(|| format!("Failed to load {}", clip.path))?;
+            let mut converter = self.clone();
+            converter.animation = clip.animation.clone();
+            bone_infos.extend(converter.output_bone_infos(&scene));
+
+            let (keyframes, clip_report) = converter.prepare_scene_keyframes(&scene);
+            if keyframes.is_empty() {
+                warn!("{} has no keyframes", clip);
+            }
+            for removed in clip_report.removed_bones {
+                if !report.removed_bones.contains(&removed) {
+                    report.removed_bones.push(removed);
+                }
+            }
+            if report.root_motion.is_none() {
+                report.root_motion = clip_report.root_motion;
+            }
+            sequences.push(keyframes);
+        }
+
+        let mut keyframes = clips::concatenate(&sequences, config);
+        self.finish_keyframes(&mut keyframes, &mut report);
+        self.build_sequence(&keyframes, &bone_infos, report)
+    }
+
+    /// Convert one long take into a KeyframeSequence per clip range
+    ///
+    /// The per-clip stages run once on the whole take, except for the time operations:
+    /// ranges are in the take's own time, so each slice gets the time operations and then
+    /// the whole-sequence stages on its own. Returns the clip names with their sequences.
+    pub fn convert_file_split(
+        &self,
+        file_path: &str,
+        ranges: &clips::ClipRanges,
+    ) -> Result<Vec<(String, WeakDom, ConversionReport)>> {
+        let scene = Scene::from_file(file_path, vec![])?;
+        let bone_infos = self.output_bone_infos(&scene);
+        let (keyframes, report) = self.prepare_source_keyframes(&scene);
+
+        ranges
+            .ranges
+            .iter()
+            .map(|range| {
+                let mut clip = clips::slice_keyframes(&keyframes, range.start, range.end);
+                self.apply_time_operations(&mut clip);
+                let mut clip_report = ConversionReport {
+                    root_motion: report
+                        .root_motion
+                        .as_ref()
+                        .map(|track| track.slice(range.start, range.end)),
+                    removed_bones: report.removed_bones.clone(),
+                    ..Default::default()
+                };
+                self.finish_keyframes(&mut clip, &mut clip_report);
+                let (sequence, clip_report) =
+                    self.build_sequence(&clip, &bone_infos, clip_report)?;
+                Ok((range.name.clone(), sequence, clip_report))
+            })
+            .collect()
+    }
+
+    /// Convert every animation in a file into its own KeyframeSequence
+    ///
+    /// Returns the animation names with their sequences, in file order. Files with a
+    /// single animation, or a converter narrowed to one with [`Self::with_animation`], give
+    /// one sequence as [`Self::convert_file_with_report`] would. Fails if several
+    /// animations share a name.
+    pub fn convert_file_per_animation(
+        &self,
+        file_path: &str,
+    ) -> Result<Vec<(String, WeakDom, ConversionReport)>> {
+        let scene = Scene::from_file(file_path, vec![])?;
+        let bone_infos = self.output_bone_infos(&scene);
+        if self.animation.is_some() || scene.animations.len() <= 1 {
+            let name = self
+                .animation
+                .clone()
+                .or_else(|| scene.animations.first().map(|anim| anim.name.clone()))
+                .unwrap_or_default();
+            let (keyframes, report) = self.convert_scene_with_report(&scene);
+            let (sequence, report) = self.build_sequence(&keyframes, &bone_infos, report)?;
+            return Ok(vec![(name, sequence, report)]);
+        }
+
+        // Animations are selected by name, so namesakes can't be converted on their own
+        let mut names = HashSet::new();
+        if let Some(animation) = scene
+            .animations
+            .iter()
+            .find(|animation| !names.insert(animation.name.as_str()))
+        {
+            bail!(
+                "Several animations in {} are named '{}', so they can't be converted separately",
+                file_path,
+                animation.name
+            );
+        }
+
+        scene
+            .animations
+            .iter()
+            .map(|animation| {
+                let converter = self.clone().with_animation(animation.name.clone());
+                let (keyframes, report) = converter.convert_scene_with_report(&scene);
+                let (sequence, report) = self.build_sequence(&keyframes, &bone_infos, report)?;
+                Ok((animation.name.clone(), sequence, report))
+            })
+            .collect()
+    }
+
+    /// Build the KeyframeSequence for finished keyframes and validate it
+    fn build_sequence(
+        &self,
+        keyframes: &[Keyframe],
+        bone_infos: &HashMap<String, NodeInfo>,
+        mut report: ConversionReport,
+    ) -> Result<(WeakDom, ConversionReport)> {
+        let mut kfs = self.keyframes_to_weakdom(keyframes, bone_infos);
+        if let Some(looping) = &report.looping {
+            converter::set_sequence_looped(&mut kfs, looping.looped);
+        }
+        if let Some(budget) = &self.budget {
+            report.validation = Some(validation::validate_sequence(keyframes, &kfs, budget)?);
+        }
+        Ok((kfs, report))
+    }
+
+    /// The bone hierarchy under the names used in the output
+    fn output_bone_infos(&self, scene: &Scene) -> HashMap<String, NodeInfo> {
+        match &self.retarget {
+            Some(spec) => spec.target_bone_infos(),
+            None => self.mapped_bone_infos(scene),
+        }
+    }
+
+    /// The source bone hierarchy after bone selection and mapping
+    fn mapped_bone_infos(&self, scene: &Scene) -> HashMap<String, NodeInfo> {
+        let bone_infos = utils::fold_fbx_helper_infos(&self.source_bone_infos(scene).0);
+        let bone_infos = match &self.bone_selection {
+            Some(selection) => selection.apply_to_bone_infos(&bone_infos),
+            None => bone_infos,
+        };
+        match &self.bone_map {
+            Some(bone_map) => bone_map.apply_to_bone_infos(&bone_infos),
+            None => bone_infos,
+        }
+    }
+
+    /// The source bone hierarchy with rest transforms from the configured rest pose source,
+    /// along with each bone's parent offset
+    fn source_bone_infos(
+        &self,
+        scene: &Scene,
+    ) -> (HashMap<String, NodeInfo>, HashMap<String, Mat4>) {
+        rest_pose::rest_bone_infos_with_offsets(scene, &self.rest_pose)
     }
 
     fn extract_keyframes(
         &self,
         scene: &Scene,
         bone_infos: &HashMap<String, NodeInfo>,
+        offsets: &HashMap<String, Mat4>,
     ) -> Vec<Keyframe> {
-        converter::extract_keyframes_from_scene(scene, bone_infos)
+        converter::extract_animation_keyframes(
+            scene,
+            bone_infos,
+            offsets,
+            self.animation.as_deref(),
+        )
     }
 
-    fn filter_identical_poses(&self, keyframes: &mut Vec<Keyframe>) {
-        converter::filter_identical_bone_poses(keyframes, self.epsilon);
+    fn filter_identical_poses(&self, keyframes: &mut Vec<Keyframe>) -> Vec<RemovedBone> {
+        converter::filter_identical_bone_poses(keyframes, self.comparison_tolerance())
     }
 }
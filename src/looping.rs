@@ -0,0 +1,186 @@
+//! Loop detection and seam preparation for cyclic clips such as walks and idles
+
+use std::collections::BTreeMap;
+
+use glam::{Quat, Vec3};
+use log::debug;
+
+use crate::types::{Keyframe, Tolerance};
+use crate::utils::{cframe_from_glam, cframe_to_glam, quat_angle_between};
+
+/// How the seam between the last and first pose is repaired
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoopSeam {
+    /// Leave the poses untouched
+    #[default]
+    None,
+    /// Replace each bone's last pose with its first pose
+    MatchStart,
+    /// Spread the seam offset over each bone's last `frames` poses
+    Blend {
+        /// Number of trailing poses to blend over
+        frames: usize,
+    },
+}
+
+/// Settings for loop detection and seam preparation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopConfig {
+    /// Largest start/end difference that still counts as a loop
+    pub tolerance: Tolerance,
+    /// How to repair the seam of a looping clip
+    pub seam: LoopSeam,
+    /// Treat the clip as looping even if the start and end poses differ
+    pub force: bool,
+}
+
+impl Default for LoopConfig {
+    fn default() -> Self {
+        Self {
+            tolerance: Tolerance::new(0.05, 5.0),
+            seam: LoopSeam::None,
+            force: false,
+        }
+    }
+}
+
+/// Result of comparing a clip's first and last poses
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoopReport {
+    /// Loopability between 0 and 1: 1 for a perfect seam, 0.5 at the tolerance boundary
+    pub score: f32,
+    /// Largest start/end position difference of any bone, in studs
+    pub max_position_error: f32,
+    /// Largest start/end rotation difference of any bone, in degrees
+    pub max_angle_error: f32,
+    /// The bone with the worst seam, if any bone was compared
+    pub worst_bone: Option<String>,
+    /// Whether the clip is treated as looping
+    pub looped: bool,
+    /// Whether the seam was modified
+    pub seam_fixed: bool,
+}
+
+/// First and last pose of a bone, with the keyframe indices they came from
+struct BoneSeam {
+    indices: Vec<usize>,
+    start: (Vec3, Quat),
+    end: (Vec3, Quat),
+}
+
+/// Compare every bone's first and last pose to judge how well the clip loops
+pub fn detect_loop(keyframes: &[Keyframe], tolerance: Tolerance) -> LoopReport {
+    let mut report = LoopReport {
+        score: 1.0,
+        ..Default::default()
+    };
+    let mut worst_exceedance = 0.0_f32;
+
+    for (name, seam) in collect_seams(keyframes) {
+        if seam.indices.len() < 2 {
+            continue;
+        }
+
+        let position_error = seam.start.0.distance(seam.end.0);
+        let angle_error = quat_angle_between(seam.start.1, seam.end.1).to_degrees();
+        report.max_position_error = report.max_position_error.max(position_error);
+        report.max_angle_error = report.max_angle_error.max(angle_error);
+
+        let exceedance = tolerance.exceedance(position_error, angle_error);
+        if report.worst_bone.is_none() || exceedance > worst_exceedance {
+            worst_exceedance = exceedance;
+            report.worst_bone = Some(name);
+        }
+    }
+
+    report.score = 1.0 / (1.0 + worst_exceedance);
+    report.looped = worst_exceedance <= 1.0;
+
+    debug!(
+        "Loop detection: score {:.3}, max error {} studs / {} degrees (worst bone: {:?})",
+        report.score, report.max_position_error, report.max_angle_error, report.worst_bone
+    );
+
+    report
+}
+
+/// Detect whether the clip loops and, if it does, repair the seam as configured
+pub fn prepare_loop(keyframes: &mut [Keyframe], config: LoopConfig) -> LoopReport {
+    let mut report = detect_loop(keyframes, config.tolerance);
+    report.looped |= config.force;
+
+    if !report.looped || config.seam == LoopSeam::None {
+        return report;
+    }
+
+    for (name, seam) in collect_seams(keyframes) {
+        let count = seam.indices.len();
+        if count < 2 {
+            continue;
+        }
+
+        let (start_position, start_rotation) = seam.start;
+        let (end_position, end_rotation) = seam.end;
+        let position_offset = start_position - end_position;
+        let rotation_offset = start_rotation * end_rotation.inverse();
+
+        let frames = match config.seam {
+            LoopSeam::None => continue,
+            LoopSeam::MatchStart => 1,
+            LoopSeam::Blend { frames } => frames.clamp(1, count - 1),
+        };
+
+        // Ramp the offset from 0 just before the blend window to 1 at the last pose
+        let blend_indices = &seam.indices[count - frames..];
+        let window_start = keyframes[seam.indices[count - frames - 1]].time;
+        let window_end = keyframes[seam.indices[count - 1]].time;
+        let span = window_end - window_start;
+
+        for &index in blend_indices {
+            let keyframe = &mut keyframes[index];
+            let weight = if span > 0.0 {
+                ((keyframe.time - window_start) / span) as f32
+            } else {
+                1.0
+            };
+
+            if let Some(pose) = keyframe.poses.iter_mut().find(|p| p.name == name) {
+                let (position, rotation) = cframe_to_glam(&pose.cframe);
+                pose.cframe = cframe_from_glam(
+                    position + position_offset * weight,
+                    Quat::IDENTITY.slerp(rotation_offset, weight) * rotation,
+                );
+            }
+        }
+    }
+
+    report.seam_fixed = true;
+    debug!("Loop seam repaired with {:?}", config.seam);
+
+    report
+}
+
+/// Collect each bone's first and last pose in time order
+fn collect_seams(keyframes: &[Keyframe]) -> BTreeMap<String, BoneSeam> {
+    let mut order: Vec<usize> = (0..keyframes.len()).collect();
+    order.sort_by(|&a, &b| keyframes[a].time.total_cmp(&keyframes[b].time));
+
+    let mut seams: BTreeMap<String, BoneSeam> = BTreeMap::new();
+    for index in order {
+        for pose in &keyframes[index].poses {
+            let transform = cframe_to_glam(&pose.cframe);
+            seams
+                .entry(pose.name.clone())
+                .and_modify(|seam| {
+                    seam.indices.push(index);
+                    seam.end = transform;
+                })
+                .or_insert_with(|| BoneSeam {
+                    indices: vec![index],
+                    start: transform,
+                    end: transform,
+                });
+        }
+    }
+    seams
+}
@@ -0,0 +1,240 @@
+//! Left/right mirroring of animations
+//!
+//! Mirroring happens in model space: every bone's change from its rest pose is reflected
+//! across the mirror plane and applied to its counterpart's rest pose, so bones whose rest
+//! orientations are not themselves mirror images still mirror correctly.
+
+use std::collections::HashMap;
+
+use glam::{Quat, Vec3};
+use log::{debug, warn};
+
+use crate::fk::{Transform, global_transforms};
+use crate::types::{Keyframe, NodeInfo};
+use crate::utils::{cframe_from_glam, collect_pose_tracks, decompose_matrix4x4, sample_pose_track};
+
+/// The plane animations are reflected across, named by its normal axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MirrorPlane {
+    /// Reflect across the YZ plane, flipping X
+    #[default]
+    X,
+    /// Reflect across the XZ plane, flipping Y
+    Y,
+    /// Reflect across the XY plane, flipping Z
+    Z,
+}
+
+impl MirrorPlane {
+    fn reflect_vector(&self, v: Vec3) -> Vec3 {
+        match self {
+            Self::X => Vec3::new(-v.x, v.y, v.z),
+            Self::Y => Vec3::new(v.x, -v.y, v.z),
+            Self::Z => Vec3::new(v.x, v.y, -v.z),
+        }
+    }
+
+    /// The rotation seen in the mirror, i.e. the reflection conjugated with `q`
+    fn reflect_rotation(&self, q: Quat) -> Quat {
+        match self {
+            Self::X => Quat::from_xyzw(q.x, -q.y, -q.z, q.w),
+            Self::Y => Quat::from_xyzw(-q.x, q.y, -q.z, q.w),
+            Self::Z => Quat::from_xyzw(-q.x, -q.y, q.z, q.w),
+        }
+    }
+}
+
+/// Settings for mirroring an animation
+#[derive(Debug, Clone, PartialEq)]
+pub struct MirrorConfig {
+    /// The plane to reflect across, in the skeleton's model space
+    pub plane: MirrorPlane,
+    /// Name fragments identifying counterpart bones, tried in order
+    pub name_pairs: Vec<(String, String)>,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        let name_pairs = [
+            ("Left", "Right"),
+            ("left", "right"),
+            ("_L", "_R"),
+            (".L", ".R"),
+            ("_l", "_r"),
+            (".l", ".r"),
+        ];
+        Self {
+            plane: MirrorPlane::X,
+            name_pairs: name_pairs
+                .iter()
+                .map(|&(left, right)| (left.to_owned(), right.to_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl MirrorConfig {
+    /// The most likely counterpart of a bone name, or `None` if no name pair matches
+    pub fn counterpart_name(&self, name: &str) -> Option<String> {
+        self.counterpart_candidates(name).into_iter().next()
+    }
+
+    /// Every name a bone's counterpart could have, most likely first
+    ///
+    /// Each occurrence of each pair's fragments is swapped on its own. Swaps at the start
+    /// or end of the name come before swaps inside it, so `leg_lower_l` pairs with
+    /// `leg_lower_r` ahead of `leg_rower_l`; ties keep the order of `name_pairs`.
+    pub fn counterpart_candidates(&self, name: &str) -> Vec<String> {
+        let mut candidates: Vec<(bool, String)> = Vec::new();
+        for (left, right) in &self.name_pairs {
+            for (from, to) in [(left, right), (right, left)] {
+                if from.is_empty() {
+                    continue;
+                }
+                for (index, _) in name.match_indices(from.as_str()) {
+                    let end = index + from.len();
+                    let candidate = format!("{}{}{}", &name[..index], to, &name[end..]);
+                    if candidates
+                        .iter()
+                        .all(|(_, existing)| *existing != candidate)
+                    {
+                        candidates.push((index > 0 && end < name.len(), candidate));
+                    }
+                }
+            }
+        }
+        candidates.sort_by_key(|(inside, _)| *inside);
+        candidates
+            .into_iter()
+            .map(|(_, candidate)| candidate)
+            .collect()
+    }
+
+    /// Pair every bone in a hierarchy with its counterpart, or with itself if it has none
+    ///
+    /// Two bones are paired only when each is the other's most likely counterpart in the
+    /// hierarchy, so the pairing is always symmetric. A bone whose counterpart pairs with
+    /// a different bone is left unpaired.
+    pub fn counterparts<'a>(
+        &self,
+        bone_infos: &'a HashMap<String, NodeInfo>,
+    ) -> HashMap<&'a str, &'a str> {
+        let best: HashMap<&str, &str> = bone_infos
+            .keys()
+            .filter_map(|name| {
+                let counterpart = self
+                    .counterpart_candidates(name)
+                    .into_iter()
+                    .find_map(|counterpart| bone_infos.get_key_value(&counterpart))?;
+                Some((name.as_str(), counterpart.0.as_str()))
+            })
+            .collect();
+
+        let mut names: Vec<&str> = bone_infos.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+            .into_iter()
+            .map(|name| {
+                let Some(&counterpart) = best.get(name) else {
+                    return (name, name);
+                };
+                if best.get(counterpart) == Some(&name) {
+                    if name < counterpart {
+                        debug!("Mirror: {} <-> {}", name, counterpart);
+                    }
+                    (name, counterpart)
+                } else {
+                    warn!(
+                        "Mirror: {} matches {}, which pairs with another bone; leaving {} unpaired",
+                        name, counterpart, name
+                    );
+                    (name, name)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Mirror keyframes across a plane, swapping the tracks of left/right bone pairs
+///
+/// `bone_infos` must describe the hierarchy under the names used by `keyframes`. Bones
+/// are paired by [`MirrorConfig::counterparts`]; unpaired bones are mirrored onto
+/// themselves.
+pub fn mirror_keyframes(
+    keyframes: &mut [Keyframe],
+    bone_infos: &HashMap<String, NodeInfo>,
+    config: &MirrorConfig,
+) {
+    let counterparts = config.counterparts(bone_infos);
+
+    let rest_locals: HashMap<&str, Transform> = bone_infos
+        .iter()
+        .map(|(name, info)| {
+            let (translation, rotation) = decompose_matrix4x4(&info.rest_transform);
+            (
+                name.as_str(),
+                Transform {
+                    translation,
+                    rotation,
+                },
+            )
+        })
+        .collect();
+    let rest_globals = global_transforms(bone_infos, &|name| rest_locals[name]);
+
+    let original = keyframes.to_vec();
+    let tracks = collect_pose_tracks(&original);
+    let plane = config.plane;
+
+    for keyframe in keyframes.iter_mut() {
+        let time = keyframe.time;
+        let globals = global_transforms(bone_infos, &|name| {
+            let rest = rest_locals[name];
+            match tracks.get(name) {
+                Some(track) => {
+                    let (position, rotation) = sample_pose_track(track, time);
+                    Transform {
+                        translation: rest.translation + position,
+                        rotation: rest.rotation * rotation,
+                    }
+                }
+                None => rest,
+            }
+        });
+
+        // A bone's mirrored model-space transform, taken from its counterpart's motion
+        let mirrored = |name: &str| -> Transform {
+            let source = counterparts[name];
+            let current = globals[source];
+            let source_rest = rest_globals[source];
+            let rest = rest_globals[name];
+            Transform {
+                translation: rest.translation
+                    + plane.reflect_vector(current.translation - source_rest.translation),
+                rotation: plane.reflect_rotation(current.rotation * source_rest.rotation.inverse())
+                    * rest.rotation,
+            }
+        };
+
+        for pose in &mut keyframe.poses {
+            let Some(&target) = counterparts.get(pose.name.as_str()) else {
+                continue;
+            };
+
+            let parent = bone_infos[target]
+                .parent
+                .as_deref()
+                .filter(|parent| bone_infos.contains_key(*parent))
+                .map(mirrored)
+                .unwrap_or(Transform::IDENTITY);
+            let local = parent.inverse().mul(&mirrored(target));
+            let rest = rest_locals[target];
+
+            pose.name = target.to_owned();
+            pose.cframe = cframe_from_glam(
+                local.translation - rest.translation,
+                (rest.rotation.inverse() * local.rotation).normalize(),
+            );
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Optimization passes that shrink keyframe data without visibly changing playback
+
+use std::collections::{BTreeMap, HashSet};
+
+use glam::{Quat, Vec3};
+use log::debug;
+
+use crate::types::{Keyframe, Tolerance};
+use crate::utils::{cframe_from_glam, cframe_to_glam, quat_angle_between};
+
+/// Precision that pose values are rounded to by [`quantize_keyframes`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quantization {
+    /// Position step in studs, or 0 to leave positions untouched
+    pub position_step: f32,
+    /// Rotation step in degrees, or 0 to leave rotations untouched
+    pub angle_step: f32,
+}
+
+impl Quantization {
+    /// Create a new quantization from a position (studs) and angle (degrees) step
+    pub fn new(position_step: f32, angle_step: f32) -> Self {
+        Self {
+            position_step,
+            angle_step,
+        }
+    }
+}
+
+impl Default for Quantization {
+    fn default() -> Self {
+        Self {
+            position_step: 0.001,
+            angle_step: 0.01,
+        }
+    }
+}
+
+/// Pose reduction statistics for a single bone
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoneReduction {
+    /// The name of the bone
+    pub name: String,
+    /// Number of poses the bone had before reduction
+    pub poses_before: usize,
+    /// Number of poses the bone kept after reduction
+    pub poses_after: usize,
+}
+
+/// Summary of a keyframe reduction pass
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReductionReport {
+    /// Per-bone statistics, sorted by bone name
+    pub bones: Vec<BoneReduction>,
+    /// Number of keyframes before reduction
+    pub keyframes_before: usize,
+    /// Number of keyframes after reduction
+    pub keyframes_after: usize,
+}
+
+impl ReductionReport {
+    /// Total number of poses before reduction
+    pub fn poses_before(&self) -> usize {
+        self.bones.iter().map(|bone| bone.poses_before).sum()
+    }
+
+    /// Total number of poses after reduction
+    pub fn poses_after(&self) -> usize {
+        self.bones.iter().map(|bone| bone.poses_after).sum()
+    }
+
+    /// Fraction of poses that were removed, between 0 and 1
+    pub fn removed_ratio(&self) -> f64 {
+        let before = self.poses_before();
+        if before == 0 {
+            return 0.0;
+        }
+        1.0 - self.poses_after() as f64 / before as f64
+    }
+}
+
+/// Round every pose to the given precision
+///
+/// Positions are snapped to multiples of the position step. Rotations are snapped in
+/// rotation-vector space, so they are rebuilt from an axis and angle and stay orthonormal.
+pub fn quantize_keyframes(keyframes: &mut [Keyframe], quantization: Quantization) {
+    for keyframe in keyframes.iter_mut() {
+        for pose in &mut keyframe.poses {
+            let (position, rotation) = cframe_to_glam(&pose.cframe);
+            pose.cframe = cframe_from_glam(
+                quantize_position(position, quantization.position_step),
+                quantize_rotation(rotation, quantization.angle_step),
+            );
+        }
+    }
+
+    debug!(
+        "Quantized {} keyframes to {} studs and {} degrees",
+        keyframes.len(),
+        quantization.position_step,
+        quantization.angle_step
+    );
+}
+
+fn quantize_position(position: Vec3, step: f32) -> Vec3 {
+    if step <= 0.0 {
+        return position;
+    }
+    (position / step).round() * step
+}
+
+fn quantize_rotation(rotation: Quat, step_degrees: f32) -> Quat {
+    if step_degrees <= 0.0 {
+        return rotation;
+    }
+
+    // Keep the shortest-arc representation so the rotation vector angle stays within 180 degrees
+    let rotation = if rotation.w < 0.0 {
+        -rotation
+    } else {
+        rotation
+    };
+    let step = step_degrees.to_radians();
+    let scaled_axis = (rotation.to_scaled_axis() / step).round() * step;
+    Quat::from_scaled_axis(scaled_axis)
+}
+
+/// A single pose of a bone track, decomposed for interpolation
+struct TrackKey {
+    keyframe_index: usize,
+    time: f64,
+    position: Vec3,
+    rotation: Quat,
+}
+
+/// Remove poses that linear interpolation between their neighbours reproduces within tolerance
+///
+/// Each bone track is simplified independently with the Ramer–Douglas–Peucker algorithm,
+/// lerping positions and slerping rotations the way Roblox blends between a bone's poses.
+/// The first and last pose of every bone are always kept, and keyframes left without
+/// poses are removed.
+pub fn reduce_keyframes(keyframes: &mut Vec<Keyframe>, tolerance: Tolerance) -> ReductionReport {
+    let keyframes_before = keyframes.len();
+
+    // Collect every bone's poses into a track ordered by time
+    let mut tracks: BTreeMap<String, Vec<TrackKey>> = BTreeMap::new();
+    for (keyframe_index, keyframe) in keyframes.iter().enumerate() {
+        for pose in &keyframe.poses {
+            let (position, rotation) = cframe_to_glam(&pose.cframe);
+            tracks.entry(pose.name.clone()).or_default().push(TrackKey {
+                keyframe_index,
+                time: keyframe.time,
+                position,
+                rotation,
+            });
+        }
+    }
+
+    let mut removed_poses: Vec<HashSet<String>> = vec![HashSet::new(); keyframes.len()];
+    let mut bones = Vec::with_capacity(tracks.len());
+
+    for (name, mut track) in tracks {
+        track.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let keep = simplify_track(&track, tolerance);
+        let poses_after = keep.iter().filter(|&&kept| kept).count();
+
+        for (key, kept) in track.iter().zip(&keep) {
+            if !kept {
+                removed_poses[key.keyframe_index].insert(name.clone());
+            }
+        }
+
+        debug!(
+            "Bone {}: {} -> {} poses after reduction",
+            name,
+            track.len(),
+            poses_after
+        );
+
+        bones.push(BoneReduction {
+            name,
+            poses_before: track.len(),
+            poses_after,
+        });
+    }
+
+    for (keyframe, removed) in keyframes.iter_mut().zip(&removed_poses) {
+        keyframe.poses.retain(|pose| !removed.contains(&pose.name));
+    }
+    keyframes.retain(|kf| !kf.poses.is_empty());
+
+    let report = ReductionReport {
+        bones,
+        keyframes_before,
+        keyframes_after: keyframes.len(),
+    };
+
+    debug!(
+        "After reduction: {} -> {} keyframes, {} -> {} poses",
+        report.keyframes_before,
+        report.keyframes_after,
+        report.poses_before(),
+        report.poses_after()
+    );
+
+    report
+}
+
+/// Run Ramer–Douglas–Peucker over a track, returning which keys must be kept
+fn simplify_track(track: &[TrackKey], tolerance: Tolerance) -> Vec<bool> {
+    let mut keep = vec![false; track.len()];
+    if track.len() <= 2 {
+        keep.fill(true);
+        return keep;
+    }
+
+    let last = track.len() - 1;
+    keep[0] = true;
+    keep[last] = true;
+
+    let mut segments = vec![(0, last)];
+    while let Some((start, end)) = segments.pop() {
+        // Find the key that deviates most from the interpolated segment
+        let mut worst = None;
+        let mut worst_score = 1.0;
+        for index in start + 1..end {
+            let score = interpolation_error(&track[start], &track[end], &track[index], tolerance);
+            if score > worst_score {
+                worst_score = score;
+                worst = Some(index);
+            }
+        }
+
+        if let Some(index) = worst {
+            keep[index] = true;
+            segments.push((start, index));
+            segments.push((index, end));
+        }
+    }
+
+    keep
+}
+
+/// Error of interpolating `key` between `start` and `end`, relative to the tolerance
+///
+/// Values above 1 mean the key cannot be rebuilt within tolerance.
+fn interpolation_error(
+    start: &TrackKey,
+    end: &TrackKey,
+    key: &TrackKey,
+    tolerance: Tolerance,
+) -> f32 {
+    let span = end.time - start.time;
+    let alpha = if span > 0.0 {
+        ((key.time - start.time) / span) as f32
+    } else {
+        0.0
+    };
+
+    let position = start.position.lerp(end.position, alpha);
+    let rotation = start.rotation.slerp(end.rotation, alpha);
+
+    let position_error = position.distance(key.position);
+    let angle_error = quat_angle_between(rotation, key.rotation).to_degrees();
+
+    tolerance.exceedance(position_error, angle_error)
+}
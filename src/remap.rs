@@ -0,0 +1,279 @@
+//! Bone renaming through exact names and regex rules
+//!
+//! A mapping file can be written in TOML or JSON:
+//!
+//! ```toml
+//! [bones]
+//! "mixamorig:LeftUpLeg" = "LeftUpperLeg"
+//! "mixamorig:HeadTop_End" = ""   # dropped
+//!
+//! [[rules]]
+//! pattern = "^mixamorig:(.*)$"
+//! replace = "$1"
+//! ```
+//!
+//! Exact names take precedence over rules, and rules are tried in order. An empty
+//! (or, in JSON, `null`) target drops the bone. Bones matched by nothing keep their name.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::report::{BoneRemovalReason, RemovedBone};
+use crate::types::{Keyframe, NodeInfo};
+
+/// On-disk layout of a mapping file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MappingFile {
+    #[serde(default)]
+    bones: BTreeMap<String, Option<String>>,
+    #[serde(default)]
+    rules: Vec<RuleFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    pattern: String,
+    #[serde(default)]
+    replace: Option<String>,
+}
+
+/// A compiled set of bone renaming rules
+#[derive(Debug, Clone, Default)]
+pub struct BoneMap {
+    exact: HashMap<String, Option<String>>,
+    rules: Vec<(Regex, Option<String>)>,
+}
+
+impl BoneMap {
+    /// Create an empty mapping that keeps every name
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a mapping file, choosing JSON or TOML by its extension
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read bone mapping {}", path.display()))?;
+
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let map = if is_json {
+            Self::from_json_str(&contents)
+        } else {
+            Self::from_toml_str(&contents)
+        };
+        map.with_context(|| format!("Invalid bone mapping {}", path.display()))
+    }
+
+    /// Parse a mapping from TOML
+    pub fn from_toml_str(contents: &str) -> Result<Self> {
+        Self::from_mapping_file(toml::from_str(contents)?)
+    }
+
+    /// Parse a mapping from JSON
+    pub fn from_json_str(contents: &str) -> Result<Self> {
+        Self::from_mapping_file(serde_json::from_str(contents)?)
+    }
+
+    fn from_mapping_file(file: MappingFile) -> Result<Self> {
+        let mut map = Self::new();
+        for (from, to) in file.bones {
+            map.insert(from, to);
+        }
+        for rule in file.rules {
+            map.add_rule(&rule.pattern, rule.replace)?;
+        }
+        Ok(map)
+    }
+
+    /// Map an exact bone name, or drop the bone when `to` is `None` or empty
+    pub fn insert(&mut self, from: impl Into<String>, to: Option<String>) {
+        self.exact
+            .insert(from.into(), to.filter(|name| !name.is_empty()));
+    }
+
+    /// Append a regex rule; `replace` may use capture groups such as `$1`
+    ///
+    /// A `None` or empty replacement drops every matching bone.
+    pub fn add_rule(&mut self, pattern: &str, replace: Option<String>) -> Result<()> {
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("Invalid bone mapping pattern '{}'", pattern))?;
+        self.rules
+            .push((regex, replace.filter(|name| !name.is_empty())));
+        Ok(())
+    }
+
+    /// The new name of a bone, or `None` if the bone is dropped
+    pub fn map_name(&self, name: &str) -> Option<String> {
+        if let Some(target) = self.exact.get(name) {
+            return target.clone();
+        }
+
+        for (regex, replace) in &self.rules {
+            if regex.is_match(name) {
+                return replace
+                    .as_ref()
+                    .map(|replace| regex.replace(name, replace.as_str()).into_owned());
+            }
+        }
+
+        Some(name.to_owned())
+    }
+
+    /// The source bone that keeps each new name when several bones map to it
+    ///
+    /// Bones in the hierarchy win over bones that are only posed, and ties go to the first
+    /// name in sorted order, so keyframes and hierarchy always agree on the winner.
+    fn resolve_collisions<'a>(
+        &self,
+        names: impl IntoIterator<Item = &'a str>,
+        bone_infos: &HashMap<String, NodeInfo>,
+    ) -> HashMap<String, &'a str> {
+        let mut names: Vec<&str> = names.into_iter().collect();
+        names.sort_by_key(|name| (!bone_infos.contains_key(*name), *name));
+        names.dedup();
+
+        let mut winners = HashMap::new();
+        for name in names {
+            if let Some(new_name) = self.map_name(name) {
+                winners.entry(new_name).or_insert(name);
+            }
+        }
+        winners
+    }
+
+    /// Rename every pose, dropping unmapped bones and keyframes left without poses
+    ///
+    /// When several bones map to the same name, only the one kept by
+    /// [`Self::apply_to_bone_infos`] keeps its poses. Returns the removed bones by their
+    /// original names: the unmapped ones, then the ones that lost a collision, each sorted.
+    pub fn apply_to_keyframes(
+        &self,
+        keyframes: &mut Vec<Keyframe>,
+        bone_infos: &HashMap<String, NodeInfo>,
+    ) -> Vec<RemovedBone> {
+        let posed: BTreeSet<String> = keyframes
+            .iter()
+            .flat_map(|kf| kf.poses.iter().map(|pose| pose.name.clone()))
+            .collect();
+        let winners = self.resolve_collisions(
+            posed
+                .iter()
+                .map(String::as_str)
+                .chain(bone_infos.keys().map(String::as_str)),
+            bone_infos,
+        );
+
+        let mut dropped = BTreeSet::new();
+        let mut skipped = BTreeSet::new();
+        for keyframe in keyframes.iter_mut() {
+            keyframe
+                .poses
+                .retain_mut(|pose| match self.map_name(&pose.name) {
+                    Some(new_name) => {
+                        if winners.get(&new_name) != Some(&pose.name.as_str()) {
+                            skipped.insert((pose.name.clone(), new_name));
+                            return false;
+                        }
+                        pose.name = new_name;
+                        true
+                    }
+                    None => {
+                        dropped.insert(pose.name.clone());
+                        false
+                    }
+                });
+        }
+
+        keyframes.retain(|kf| !kf.poses.is_empty());
+
+        for (name, new_name) in &skipped {
+            warn!(
+                "Bone {} maps to {}, which is already taken by {}; skipping",
+                name, new_name, winners[new_name]
+            );
+        }
+        for name in &dropped {
+            debug!("Dropped unmapped bone {}", name);
+        }
+
+        let unmapped = dropped.into_iter().map(|name| RemovedBone {
+            name,
+            reason: BoneRemovalReason::Unmapped,
+        });
+        let collided = skipped
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .map(|name| RemovedBone {
+                name,
+                reason: BoneRemovalReason::MappingCollision,
+            });
+        unmapped.chain(collided).collect()
+    }
+
+    /// Rename the bone hierarchy so parent lookups use the new names
+    ///
+    /// Dropped bones are removed and their children are attached to the nearest
+    /// surviving ancestor.
+    pub fn apply_to_bone_infos(
+        &self,
+        bone_infos: &HashMap<String, NodeInfo>,
+    ) -> HashMap<String, NodeInfo> {
+        let winners = self.resolve_collisions(bone_infos.keys().map(String::as_str), bone_infos);
+        let mut names: Vec<&String> = bone_infos.keys().collect();
+        names.sort();
+
+        let mut mapped = HashMap::new();
+        for name in names {
+            let Some(new_name) = self.map_name(name) else {
+                continue;
+            };
+            if winners[&new_name] != name.as_str() {
+                warn!(
+                    "Bone {} maps to {}, which is already taken by {}; skipping",
+                    name, new_name, winners[&new_name]
+                );
+                continue;
+            }
+
+            let info = &bone_infos[name];
+
+            // Walk up to the nearest ancestor that survives the mapping
+            let mut ancestor = info.parent.clone();
+            let parent = loop {
+                match ancestor {
+                    None => break None,
+                    Some(ancestor_name) => match self.map_name(&ancestor_name) {
+                        Some(mapped_name) => break Some(mapped_name),
+                        None => {
+                            ancestor = bone_infos
+                                .get(&ancestor_name)
+                                .and_then(|ancestor_info| ancestor_info.parent.clone())
+                        }
+                    },
+                }
+            };
+
+            mapped.insert(
+                new_name,
+                NodeInfo {
+                    parent,
+                    ..info.clone()
+                },
+            );
+        }
+
+        mapped
+    }
+}
@@ -0,0 +1,60 @@
+//! Reports describing what a conversion did to the animation data
+
+use crate::looping::LoopReport;
+use crate::optimize::ReductionReport;
+use crate::root_motion::RootMotionTrack;
+use crate::smoothing::SmoothingReport;
+use crate::validation::ValidationReport;
+
+/// The filtering rule that caused a bone to be removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoneRemovalReason {
+    /// All of the bone's poses were identical to each other
+    IdenticalPoses,
+    /// All of the bone's poses stayed at the rest pose
+    RestPose,
+    /// The bone mapping mapped the bone to nothing
+    Unmapped,
+    /// The bone mapping gave the bone a name another bone kept
+    MappingCollision,
+    /// The bone selection patterns excluded the bone
+    Excluded,
+}
+
+impl std::fmt::Display for BoneRemovalReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::IdenticalPoses => write!(f, "identical poses"),
+            Self::RestPose => write!(f, "rest pose"),
+            Self::Unmapped => write!(f, "mapped to nothing"),
+            Self::MappingCollision => write!(f, "mapped to a name already taken"),
+            Self::Excluded => write!(f, "excluded by pattern"),
+        }
+    }
+}
+
+/// A bone that was removed from the animation
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemovedBone {
+    /// The name of the removed bone
+    pub name: String,
+    /// The rule that removed it
+    pub reason: BoneRemovalReason,
+}
+
+/// Summary of the optional processing stages applied during a conversion
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConversionReport {
+    /// Smoothed tracks and repaired spikes, if smoothing ran
+    pub smoothing: Option<SmoothingReport>,
+    /// Motion removed from the root bone, if root motion extraction ran
+    pub root_motion: Option<RootMotionTrack>,
+    /// Result of loop detection, if loop mode was enabled
+    pub looping: Option<LoopReport>,
+    /// Bones removed by the pose filters, in the order they were removed
+    pub removed_bones: Vec<RemovedBone>,
+    /// Result of the keyframe reduction pass, if it ran
+    pub reduction: Option<ReductionReport>,
+    /// Measurements and budget checks of the output sequence, if validation ran
+    pub validation: Option<ValidationReport>,
+}
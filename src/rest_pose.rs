@@ -0,0 +1,231 @@
+//! Selectable sources for the rest pose that animation is expressed relative to
+//!
+//! Poses are stored as offsets from each bone's rest transform, so a rest pose that does
+//! not match the rig the clip is played on shows up as a constant offset in every frame.
+//! The node graph is right for most files, but FBX exports often keep the real bind pose
+//! only in the mesh bones' offset matrices, and animation-only files have nothing better
+//! than their first frame.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use glam::{Mat4, Quat, Vec3};
+use log::debug;
+use russimp::node::Node;
+use russimp::scene::Scene;
+
+use crate::types::NodeInfo;
+use crate::utils::{get_bone_infos_with_offsets, mat4_to_matrix4x4, matrix4x4_to_mat4};
+
+/// Where bone rest transforms are taken from
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum RestPoseSource {
+    /// The node transforms of the animation file
+    #[default]
+    Node,
+    /// The bind pose, from the inverse of the mesh bones' offset matrices
+    BindPose,
+    /// The first keyframe of each animated bone
+    FirstKeyframe,
+    /// Node-local transforms by bone name, e.g. the node graph of a separate rest pose file
+    Explicit(HashMap<String, Mat4>),
+}
+
+impl RestPoseSource {
+    /// Use the node transforms of another file as the rest pose
+    pub fn from_file(file_path: &str) -> Result<Self> {
+        let scene = Scene::from_file(file_path, vec![])
+            .with_context(|| format!("Failed to load rest pose file {}", file_path))?;
+        Ok(Self::Explicit(node_local_transforms(&scene)))
+    }
+}
+
+/// Extract node information from an Assimp scene, with rest transforms from `source`
+///
+/// Bones the source has no transform for keep their node rest transform.
+pub fn rest_bone_infos(scene: &Scene, source: &RestPoseSource) -> HashMap<String, NodeInfo> {
+    rest_bone_infos_with_offsets(scene, source).0
+}
+
+/// Extract node information with rest transforms from `source`, along with each bone's
+/// parent offset as returned by [`get_bone_infos_with_offsets`]
+pub fn rest_bone_infos_with_offsets(
+    scene: &Scene,
+    source: &RestPoseSource,
+) -> (HashMap<String, NodeInfo>, HashMap<String, Mat4>) {
+    let (mut bone_infos, offsets) = get_bone_infos_with_offsets(scene);
+    match source {
+        RestPoseSource::Node => {}
+        RestPoseSource::BindPose => {
+            apply_global_rest_transforms(&mut bone_infos, &bind_pose_transforms(scene));
+        }
+        RestPoseSource::FirstKeyframe => {
+            apply_local_rest_transforms(
+                &mut bone_infos,
+                &offsets,
+                &first_keyframe_transforms(scene),
+            );
+        }
+        RestPoseSource::Explicit(locals) => {
+            apply_local_rest_transforms(&mut bone_infos, &offsets, locals);
+        }
+    }
+    (bone_infos, offsets)
+}
+
+/// Replace rest transforms with node-local transforms
+///
+/// `offsets` maps each bone's node-local parent space into its recorded parent's space,
+/// as returned by [`get_bone_infos_with_offsets`].
+pub fn apply_local_rest_transforms(
+    bone_infos: &mut HashMap<String, NodeInfo>,
+    offsets: &HashMap<String, Mat4>,
+    locals: &HashMap<String, Mat4>,
+) {
+    for (name, info) in bone_infos.iter_mut() {
+        if let Some(local) = locals.get(name) {
+            let offset = offsets.get(name).copied().unwrap_or(Mat4::IDENTITY);
+            info.rest_transform = mat4_to_matrix4x4(&(offset * *local));
+            debug!("Rest pose of {} replaced", name);
+        }
+    }
+}
+
+/// Replace rest transforms with scene-space transforms
+///
+/// Each bone with a global transform gets the rest that places it there under its parent's
+/// resolved rest. Bones without one keep their rest relative to their parent.
+pub fn apply_global_rest_transforms(
+    bone_infos: &mut HashMap<String, NodeInfo>,
+    globals: &HashMap<String, Mat4>,
+) {
+    let mut resolved = HashMap::new();
+    let names: Vec<String> = bone_infos.keys().cloned().collect();
+    for name in &names {
+        resolve_global(name, bone_infos, globals, &mut resolved);
+    }
+
+    for (name, info) in bone_infos.iter_mut() {
+        let Some(global) = globals.get(name) else {
+            continue;
+        };
+        let parent = info
+            .parent
+            .as_ref()
+            .and_then(|parent| resolved.get(parent))
+            .copied()
+            .unwrap_or(Mat4::IDENTITY);
+        info.rest_transform = mat4_to_matrix4x4(&(parent.inverse() * *global));
+        debug!("Rest pose of {} replaced", name);
+    }
+}
+
+fn resolve_global(
+    name: &str,
+    bone_infos: &HashMap<String, NodeInfo>,
+    globals: &HashMap<String, Mat4>,
+    resolved: &mut HashMap<String, Mat4>,
+) -> Mat4 {
+    if let Some(&global) = resolved.get(name) {
+        return global;
+    }
+    let Some(info) = bone_infos.get(name) else {
+        return Mat4::IDENTITY;
+    };
+    let global = match globals.get(name) {
+        Some(&global) => global,
+        None => {
+            let parent = match &info.parent {
+                Some(parent) => resolve_global(parent, bone_infos, globals, resolved),
+                None => Mat4::IDENTITY,
+            };
+            parent * matrix4x4_to_mat4(&info.rest_transform)
+        }
+    };
+    resolved.insert(name.to_owned(), global);
+    global
+}
+
+/// The local transform of every node in the scene, by name
+pub fn node_local_transforms(scene: &Scene) -> HashMap<String, Mat4> {
+    fn collect(node: &Node, locals: &mut HashMap<String, Mat4>) {
+        locals.insert(node.name.clone(), matrix4x4_to_mat4(&node.transformation));
+        for child in node.children.borrow().iter() {
+            collect(child, locals);
+        }
+    }
+
+    let mut locals = HashMap::new();
+    if let Some(root) = &scene.root {
+        collect(root, &mut locals);
+    }
+    locals
+}
+
+/// The scene-space bind pose of every skinned bone, by name
+///
+/// Offset matrices map the space of the mesh's node into bone space, so the bind pose is
+/// the mesh node's transform times the inverse offset matrix.
+pub fn bind_pose_transforms(scene: &Scene) -> HashMap<String, Mat4> {
+    fn collect(node: &Node, parent: Mat4, scene: &Scene, binds: &mut HashMap<String, Mat4>) {
+        let global = parent * matrix4x4_to_mat4(&node.transformation);
+        for &mesh_index in &node.meshes {
+            let Some(mesh) = scene.meshes.get(mesh_index as usize) else {
+                continue;
+            };
+            for bone in &mesh.bones {
+                binds
+                    .entry(bone.name.clone())
+                    .or_insert_with(|| global * matrix4x4_to_mat4(&bone.offset_matrix).inverse());
+            }
+        }
+        for child in node.children.borrow().iter() {
+            collect(child, global, scene, binds);
+        }
+    }
+
+    let mut binds = HashMap::new();
+    if let Some(root) = &scene.root {
+        collect(root, Mat4::IDENTITY, scene, &mut binds);
+    }
+    debug!("Found bind poses for {} bones", binds.len());
+    binds
+}
+
+/// The local transform of every animated node at its first keys
+///
+/// Channels without position or rotation keys keep that component of the node transform.
+pub fn first_keyframe_transforms(scene: &Scene) -> HashMap<String, Mat4> {
+    let nodes = node_local_transforms(scene);
+    let mut locals = HashMap::new();
+
+    for channel in scene.animations.iter().flat_map(|anim| &anim.channels) {
+        if locals.contains_key(&channel.name) {
+            continue;
+        }
+
+        let node = nodes.get(&channel.name).copied().unwrap_or(Mat4::IDENTITY);
+        let (scale, mut rotation, mut translation) = node.to_scale_rotation_translation();
+        if let Some(key) = channel
+            .position_keys
+            .iter()
+            .min_by(|a, b| a.time.total_cmp(&b.time))
+        {
+            translation = Vec3::new(key.value.x, key.value.y, key.value.z);
+        }
+        if let Some(key) = channel
+            .rotation_keys
+            .iter()
+            .min_by(|a, b| a.time.total_cmp(&b.time))
+        {
+            rotation = Quat::from_xyzw(key.value.x, key.value.y, key.value.z, key.value.w);
+        }
+
+        locals.insert(
+            channel.name.clone(),
+            Mat4::from_scale_rotation_translation(scale, rotation.normalize(), translation),
+        );
+    }
+
+    locals
+}
@@ -0,0 +1,385 @@
+//! Retargeting source skeletons onto Roblox rigs
+//!
+//! Source poses are relative to each bone's own rest orientation, while Roblox joints
+//! on stock rigs are axis-aligned at rest. Retargeting evaluates the source skeleton,
+//! takes each bone's world-space change from its rest pose, and expresses it relative
+//! to the parent part of the target rig, after applying the offset between the
+//! source and target rest poses. Several source bones can be folded into one
+//! target part, in which case the last bone of the chain drives the part's rotation
+//! and the first bone its translation.
+
+use std::collections::{BTreeSet, HashMap};
+use std::f32::consts::{FRAC_PI_2, PI};
+
+use glam::{Mat3, Mat4, Quat, Vec3};
+use log::{debug, warn};
+
+use crate::fk::{Transform, global_transforms};
+use crate::types::{Keyframe, NodeInfo, Pose};
+use crate::utils::{
+    cframe_from_glam, collect_pose_tracks, decompose_matrix4x4, mat4_to_matrix4x4,
+    sample_pose_track,
+};
+
+/// A part of the target rig and the source bones folded into it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetPart {
+    /// The name of the target part, used as the Pose name
+    pub name: String,
+    /// The parent part in the target rig
+    pub parent: Option<String>,
+    /// Source bones folded into this part, from parent to child
+    ///
+    /// Names are matched without their namespace prefix (e.g. `mixamorig:`). The last
+    /// bone present in the source drives the part.
+    pub sources: Vec<String>,
+    /// Whether the part also receives the translation of its first source bone
+    pub translate: bool,
+    /// Model-space rotation of the part when the source skeleton is at its rest pose
+    ///
+    /// Stock rigs rest with their arms down, so a T-pose source needs its arms raised.
+    pub rest_offset: Quat,
+    /// Rotation of the joint frame (the Motor6D `C0`/`C1` rotation) relative to the part
+    ///
+    /// Poses are expressed in this frame. R15 joints are axis-aligned, R6 joints are not.
+    pub joint_rotation: Quat,
+}
+
+impl TargetPart {
+    fn new(name: &str, parent: Option<&str>, sources: &[&str]) -> Self {
+        Self {
+            name: name.to_owned(),
+            parent: parent.map(str::to_owned),
+            sources: sources.iter().map(|&source| source.to_owned()).collect(),
+            translate: false,
+            rest_offset: Quat::IDENTITY,
+            joint_rotation: Quat::IDENTITY,
+        }
+    }
+
+    fn translated(mut self) -> Self {
+        self.translate = true;
+        self
+    }
+
+    fn with_rest_offset(mut self, rest_offset: Quat) -> Self {
+        self.rest_offset = rest_offset;
+        self
+    }
+
+    fn with_joint_rotation(mut self, joint_rotation: Quat) -> Self {
+        self.joint_rotation = joint_rotation;
+        self
+    }
+}
+
+/// A complete description of how to retarget a source skeleton onto a target rig
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetargetSpec {
+    /// Target parts, parents before children
+    pub parts: Vec<TargetPart>,
+    /// Rotation from the source character space into Roblox character space
+    pub source_rotation: Quat,
+    /// Scale from source units to studs
+    pub position_scale: f32,
+}
+
+/// Built-in retargeting presets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Mixamo skeletons onto the 15 body parts of the R15 rig
+    MixamoR15,
+    /// Mixamo skeletons onto the 6 body parts of the R6 rig
+    MixamoR6,
+}
+
+impl Preset {
+    /// The retargeting description for this preset
+    pub fn spec(&self) -> RetargetSpec {
+        match self {
+            Self::MixamoR15 => mixamo_r15(),
+            Self::MixamoR6 => mixamo_r6(),
+        }
+    }
+}
+
+/// Mixamo centimetres to studs, at 28 cm per stud
+const MIXAMO_POSITION_SCALE: f32 = 1.0 / 28.0;
+
+fn mixamo_r15() -> RetargetSpec {
+    let mut parts = vec![
+        TargetPart::new("HumanoidRootPart", None, &[]),
+        TargetPart::new("LowerTorso", Some("HumanoidRootPart"), &["Hips"]).translated(),
+        TargetPart::new(
+            "UpperTorso",
+            Some("LowerTorso"),
+            &["Spine", "Spine1", "Spine2"],
+        ),
+        TargetPart::new("Head", Some("UpperTorso"), &["Neck", "Head"]),
+    ];
+
+    for (side, raise) in [("Left", -FRAC_PI_2), ("Right", FRAC_PI_2)] {
+        let part = |name: &str| format!("{}{}", side, name);
+        let bone = |name: &str| format!("{}{}", side, name);
+        // Mixamo rests in a T-pose, with the arms raised sideways
+        let t_pose = Quat::from_rotation_z(raise);
+
+        parts.extend([
+            TargetPart::new(
+                &part("UpperArm"),
+                Some("UpperTorso"),
+                &[&bone("Shoulder"), &bone("Arm")],
+            )
+            .with_rest_offset(t_pose),
+            TargetPart::new(
+                &part("LowerArm"),
+                Some(&part("UpperArm")),
+                &[&bone("ForeArm")],
+            )
+            .with_rest_offset(t_pose),
+            TargetPart::new(&part("Hand"), Some(&part("LowerArm")), &[&bone("Hand")])
+                .with_rest_offset(t_pose),
+            TargetPart::new(&part("UpperLeg"), Some("LowerTorso"), &[&bone("UpLeg")]),
+            TargetPart::new(&part("LowerLeg"), Some(&part("UpperLeg")), &[&bone("Leg")]),
+            TargetPart::new(&part("Foot"), Some(&part("LowerLeg")), &[&bone("Foot")]),
+        ]);
+    }
+
+    RetargetSpec {
+        parts,
+        // Mixamo characters face +Z, Roblox characters face -Z
+        source_rotation: Quat::from_rotation_y(PI),
+        position_scale: MIXAMO_POSITION_SCALE,
+    }
+}
+
+/// Build an R6 rig, collapsing each limb chain into a single rigid part
+///
+/// R6 poses are named after parts, while the joints are the Torso's Motor6Ds:
+/// `RootJoint` (HumanoidRootPart to Torso), `Neck`, `Left Shoulder`, `Right Shoulder`,
+/// `Left Hip` and `Right Hip`. Only the upper segment of each limb drives the part.
+fn mixamo_r6() -> RetargetSpec {
+    // RootJoint and Neck frames point their Z axis up the spine
+    let spine_joint = Quat::from_mat3(&Mat3::from_cols(Vec3::NEG_X, Vec3::Z, Vec3::Y));
+
+    let mut parts = vec![
+        TargetPart::new("HumanoidRootPart", None, &[]),
+        TargetPart::new(
+            "Torso",
+            Some("HumanoidRootPart"),
+            &["Hips", "Spine", "Spine1", "Spine2"],
+        )
+        .translated()
+        .with_joint_rotation(spine_joint),
+        TargetPart::new("Head", Some("Torso"), &["Neck", "Head"]).with_joint_rotation(spine_joint),
+    ];
+
+    // Shoulder and hip frames point their Z axis away from the body
+    for (side, sign) in [("Left", -1.0), ("Right", 1.0)] {
+        let joint = Quat::from_rotation_y(sign * FRAC_PI_2);
+
+        parts.extend([
+            TargetPart::new(
+                &format!("{} Arm", side),
+                Some("Torso"),
+                &[&format!("{}Shoulder", side), &format!("{}Arm", side)],
+            )
+            .with_rest_offset(Quat::from_rotation_z(sign * FRAC_PI_2))
+            .with_joint_rotation(joint),
+            TargetPart::new(
+                &format!("{} Leg", side),
+                Some("Torso"),
+                &[&format!("{}UpLeg", side)],
+            )
+            .with_joint_rotation(joint),
+        ]);
+    }
+
+    RetargetSpec {
+        parts,
+        source_rotation: Quat::from_rotation_y(PI),
+        position_scale: MIXAMO_POSITION_SCALE,
+    }
+}
+
+impl RetargetSpec {
+    /// The target rig hierarchy, with identity rest transforms
+    pub fn target_bone_infos(&self) -> HashMap<String, NodeInfo> {
+        self.parts
+            .iter()
+            .map(|part| {
+                (
+                    part.name.clone(),
+                    NodeInfo {
+                        rest_transform: mat4_to_matrix4x4(&Mat4::IDENTITY),
+                        parent: part.parent.clone(),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+/// Strip a namespace prefix such as `mixamorig:` from a bone name
+pub fn strip_namespace(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Retarget keyframes of a source skeleton onto the rig described by `spec`
+///
+/// `bone_infos` must describe the source hierarchy under the names used by `keyframes`.
+/// The result has one keyframe per source keyframe time, with a pose for every target
+/// part driven by a source bone.
+pub fn retarget_keyframes(
+    keyframes: &[Keyframe],
+    bone_infos: &HashMap<String, NodeInfo>,
+    spec: &RetargetSpec,
+) -> Vec<Keyframe> {
+    // Resolve which source bones drive each target part's rotation and translation
+    let by_stripped_name: HashMap<&str, &String> = bone_infos
+        .keys()
+        .map(|name| (strip_namespace(name), name))
+        .collect();
+
+    let drivers: HashMap<&str, (&String, &String)> = spec
+        .parts
+        .iter()
+        .filter_map(|part| {
+            let mut present = part
+                .sources
+                .iter()
+                .filter_map(|source| by_stripped_name.get(source.as_str()).copied());
+            let first = present.next();
+            match first {
+                Some(first) => {
+                    let last = present.next_back().unwrap_or(first);
+                    debug!("Retarget: {} driven by {}", part.name, last);
+                    Some((part.name.as_str(), (first, last)))
+                }
+                None => {
+                    if !part.sources.is_empty() {
+                        warn!(
+                            "Retarget: no source bone found for {} (tried {:?})",
+                            part.name, part.sources
+                        );
+                    }
+                    None
+                }
+            }
+        })
+        .collect();
+
+    let unused: BTreeSet<&str> = bone_infos
+        .keys()
+        .map(String::as_str)
+        .filter(|name| {
+            let stripped = strip_namespace(name);
+            !spec
+                .parts
+                .iter()
+                .any(|part| part.sources.iter().any(|source| source == stripped))
+        })
+        .collect();
+    if !unused.is_empty() {
+        debug!("Retarget: dropping unmapped bones {:?}", unused);
+    }
+
+    let parts: HashMap<&str, &TargetPart> = spec
+        .parts
+        .iter()
+        .map(|part| (part.name.as_str(), part))
+        .collect();
+
+    let tracks = collect_pose_tracks(keyframes);
+    let rest_locals: HashMap<&str, Transform> = bone_infos
+        .iter()
+        .map(|(name, info)| {
+            let (translation, rotation) = decompose_matrix4x4(&info.rest_transform);
+            (
+                name.as_str(),
+                Transform {
+                    translation,
+                    rotation,
+                },
+            )
+        })
+        .collect();
+
+    let rest_globals = global_transforms(bone_infos, &|name| rest_locals[name]);
+
+    let source_rotation = spec.source_rotation;
+    let mut times: Vec<f64> = keyframes.iter().map(|kf| kf.time).collect();
+    times.sort_by(f64::total_cmp);
+    times.dedup();
+
+    times
+        .into_iter()
+        .map(|time| {
+            let globals = global_transforms(bone_infos, &|name| {
+                let rest = rest_locals[name];
+                match tracks.get(name) {
+                    Some(track) => {
+                        let (delta_translation, delta_rotation) = sample_pose_track(track, time);
+                        Transform {
+                            translation: rest.translation + delta_translation,
+                            rotation: rest.rotation * delta_rotation,
+                        }
+                    }
+                    None => rest,
+                }
+            });
+
+            // Model-space transform of every driven part, in Roblox space
+            let world_delta = |part: &TargetPart| -> Option<Transform> {
+                let (root, driver) = drivers.get(part.name.as_str())?;
+                let current = globals[driver.as_str()];
+                let rest = rest_globals[driver.as_str()];
+                let root_offset =
+                    globals[root.as_str()].translation - rest_globals[root.as_str()].translation;
+                Some(Transform {
+                    translation: source_rotation * root_offset * spec.position_scale,
+                    rotation: source_rotation
+                        * current.rotation
+                        * rest.rotation.inverse()
+                        * source_rotation.inverse()
+                        * part.rest_offset,
+                })
+            };
+
+            let poses = spec
+                .parts
+                .iter()
+                .filter_map(|part| {
+                    let delta = world_delta(part)?;
+                    let parent_delta = part
+                        .parent
+                        .as_deref()
+                        .and_then(|parent| parts.get(parent))
+                        .and_then(|parent| world_delta(parent))
+                        .unwrap_or(Transform::IDENTITY);
+
+                    let parent_inverse = parent_delta.rotation.inverse();
+                    let rotation = parent_inverse * delta.rotation;
+                    let translation = if part.translate {
+                        parent_inverse * (delta.translation - parent_delta.translation)
+                    } else {
+                        Vec3::ZERO
+                    };
+
+                    // Express the part-space transform in the joint frame
+                    let joint_inverse = part.joint_rotation.inverse();
+                    Some(Pose::new(
+                        part.name.clone(),
+                        cframe_from_glam(
+                            joint_inverse * translation,
+                            (joint_inverse * rotation * part.joint_rotation).normalize(),
+                        ),
+                    ))
+                })
+                .collect();
+
+            Keyframe { time, poses }
+        })
+        .filter(|kf| !kf.poses.is_empty())
+        .collect()
+}
@@ -0,0 +1,277 @@
+//! Root motion extraction for in-place locomotion clips
+//!
+//! The root bone's travel is removed from its poses and returned as a separate track
+//! that gameplay code can apply to the character instead.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use glam::{Quat, Vec3};
+use log::{debug, warn};
+
+use crate::types::{Keyframe, NodeInfo};
+use crate::utils::{cframe_from_glam, cframe_to_glam, decompose_matrix4x4};
+
+/// Which components of the root bone's motion are extracted
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootMotionConfig {
+    /// The root bone, or `None` to use the top-most animated bone
+    pub bone: Option<String>,
+    /// Extract motion along the X and Z axes
+    pub horizontal: bool,
+    /// Extract motion along the Y axis
+    pub vertical: bool,
+    /// Extract rotation about the Y axis of the root bone's parent
+    pub yaw: bool,
+}
+
+impl Default for RootMotionConfig {
+    fn default() -> Self {
+        Self {
+            bone: None,
+            horizontal: true,
+            vertical: false,
+            yaw: false,
+        }
+    }
+}
+
+/// The root motion at one point in time, relative to the first root pose
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RootMotionSample {
+    /// Time in seconds
+    pub time: f64,
+    /// Extracted translation
+    pub position: Vec3,
+    /// Extracted rotation about the Y axis, in radians
+    pub yaw: f32,
+}
+
+/// The motion removed from the root bone
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RootMotionTrack {
+    /// The bone the motion was extracted from
+    pub bone: String,
+    /// One sample per keyframe that posed the root bone, plus a slice's boundaries, in time order
+    pub samples: Vec<RootMotionSample>,
+}
+
+impl RootMotionTrack {
+    /// The extracted translation between the first and last sample
+    pub fn total_displacement(&self) -> Vec3 {
+        self.samples
+            .last()
+            .map(|sample| sample.position)
+            .unwrap_or(Vec3::ZERO)
+    }
+
+    /// The samples between `start` and `end` seconds, re-zeroed in time and motion
+    ///
+    /// The slice starts and ends with the motion interpolated at `start` and `end`, so it
+    /// is zeroed where the range begins rather than at its first sample.
+    pub fn slice(&self, start: f64, end: f64) -> Self {
+        let (Some(first), Some(last)) = (self.sample_at(start), self.sample_at(end)) else {
+            return Self {
+                bone: self.bone.clone(),
+                samples: Vec::new(),
+            };
+        };
+
+        let inner = self
+            .samples
+            .iter()
+            .filter(|sample| sample.time > start && sample.time < end)
+            .copied();
+        let samples = std::iter::once(first)
+            .chain(inner)
+            .chain((end > start).then_some(last))
+            .map(|sample| RootMotionSample {
+                time: sample.time - start,
+                position: sample.position - first.position,
+                yaw: sample.yaw - first.yaw,
+            })
+            .collect();
+        Self {
+            bone: self.bone.clone(),
+            samples,
+        }
+    }
+
+    /// The motion at `time`, interpolated between samples and held past either end
+    fn sample_at(&self, time: f64) -> Option<RootMotionSample> {
+        let next = self.samples.partition_point(|sample| sample.time <= time);
+        let (position, yaw) = match (next.checked_sub(1), self.samples.get(next)) {
+            (Some(previous), Some(next)) => {
+                let previous = &self.samples[previous];
+                let alpha = ((time - previous.time) / (next.time - previous.time)) as f32;
+                (
+                    previous.position.lerp(next.position, alpha),
+                    previous.yaw + (next.yaw - previous.yaw) * alpha,
+                )
+            }
+            (Some(previous), None) => (self.samples[previous].position, self.samples[previous].yaw),
+            (None, _) => {
+                let first = self.samples.first()?;
+                (first.position, first.yaw)
+            }
+        };
+        Some(RootMotionSample {
+            time,
+            position,
+            yaw,
+        })
+    }
+
+    /// Render the track as a Luau module returning the bone name and CFrame samples
+    pub fn to_luau(&self) -> String {
+        let mut luau = String::new();
+        let _ = writeln!(luau, "-- Root motion extracted by anim2rbx");
+        let _ = writeln!(luau, "return {{");
+        let _ = writeln!(luau, "\tbone = {:?},", self.bone);
+        let _ = writeln!(luau, "\tsamples = {{");
+        for sample in &self.samples {
+            let _ = writeln!(
+                luau,
+                "\t\t{{ time = {}, cframe = CFrame.new({}, {}, {}) * CFrame.Angles(0, {}, 0) }},",
+                sample.time, sample.position.x, sample.position.y, sample.position.z, sample.yaw
+            );
+        }
+        let _ = writeln!(luau, "\t}},");
+        let _ = writeln!(luau, "}}");
+        luau
+    }
+
+    /// Render the track as JSON
+    pub fn to_json(&self) -> String {
+        let samples: Vec<_> = self
+            .samples
+            .iter()
+            .map(|sample| {
+                serde_json::json!({
+                    "time": sample.time,
+                    "position": [sample.position.x, sample.position.y, sample.position.z],
+                    "yaw": sample.yaw,
+                })
+            })
+            .collect();
+        let track = serde_json::json!({ "bone": self.bone, "samples": samples });
+        serde_json::to_string_pretty(&track).unwrap_or_default()
+    }
+}
+
+/// Find the top-most posed bone: the first one, by name, with no posed ancestor
+pub fn find_root_bone(
+    keyframes: &[Keyframe],
+    bone_infos: &HashMap<String, NodeInfo>,
+) -> Option<String> {
+    let posed: BTreeSet<&str> = keyframes
+        .iter()
+        .flat_map(|kf| kf.poses.iter().map(|pose| pose.name.as_str()))
+        .collect();
+
+    posed
+        .iter()
+        .find(|&&name| {
+            let mut ancestor = bone_infos.get(name).and_then(|info| info.parent.as_deref());
+            while let Some(name) = ancestor {
+                if posed.contains(&name) {
+                    return false;
+                }
+                ancestor = bone_infos.get(name).and_then(|info| info.parent.as_deref());
+            }
+            true
+        })
+        .map(|&name| name.to_owned())
+}
+
+/// Remove the configured root motion components from the root bone's poses
+///
+/// The root keeps its first pose's offset, so the clip stays where it starts. Returns
+/// `None` if no root bone could be found.
+pub fn extract_root_motion(
+    keyframes: &mut [Keyframe],
+    bone_infos: &HashMap<String, NodeInfo>,
+    config: &RootMotionConfig,
+) -> Option<RootMotionTrack> {
+    let bone = match &config.bone {
+        Some(bone) => bone.clone(),
+        None => find_root_bone(keyframes, bone_infos)?,
+    };
+
+    let mut order: Vec<usize> = (0..keyframes.len()).collect();
+    order.sort_by(|&a, &b| keyframes[a].time.total_cmp(&keyframes[b].time));
+
+    let mut track = RootMotionTrack {
+        bone: bone.clone(),
+        samples: Vec::new(),
+    };
+    // Yaw is measured about the parent's Y axis, so a tilted rest pose doesn't skew it
+    let rest_rotation = bone_infos
+        .get(&bone)
+        .map(|info| decompose_matrix4x4(&info.rest_transform).1)
+        .unwrap_or(Quat::IDENTITY);
+    let mut start: Option<(Vec3, f32)> = None;
+    let mut previous_yaw = 0.0_f32;
+
+    for index in order {
+        let keyframe = &mut keyframes[index];
+        let Some(pose) = keyframe.poses.iter_mut().find(|pose| pose.name == bone) else {
+            continue;
+        };
+
+        let (position, rotation) = cframe_to_glam(&pose.cframe);
+
+        // Unwrap yaw so turning clips accumulate past a half turn
+        let mut yaw = yaw_of(rest_rotation * rotation * rest_rotation.inverse());
+        if start.is_some() {
+            yaw = previous_yaw + wrap_angle(yaw - previous_yaw);
+        }
+        previous_yaw = yaw;
+        let (start_position, start_yaw) = *start.get_or_insert((position, yaw));
+
+        let offset = position - start_position;
+        let extracted = Vec3::new(
+            if config.horizontal { offset.x } else { 0.0 },
+            if config.vertical { offset.y } else { 0.0 },
+            if config.horizontal { offset.z } else { 0.0 },
+        );
+        let extracted_yaw = if config.yaw { yaw - start_yaw } else { 0.0 };
+
+        pose.cframe = cframe_from_glam(
+            position - extracted,
+            rest_rotation.inverse()
+                * Quat::from_rotation_y(-extracted_yaw)
+                * rest_rotation
+                * rotation,
+        );
+        track.samples.push(RootMotionSample {
+            time: keyframe.time,
+            position: extracted,
+            yaw: extracted_yaw,
+        });
+    }
+
+    if track.samples.is_empty() {
+        warn!("Root bone {} has no poses; no root motion extracted", bone);
+        return None;
+    }
+
+    debug!(
+        "Extracted root motion from {}: displacement {:?}",
+        track.bone,
+        track.total_displacement()
+    );
+
+    Some(track)
+}
+
+/// Rotation about the Y axis, from the twist part of a swing-twist decomposition
+fn yaw_of(rotation: Quat) -> f32 {
+    2.0 * rotation.y.atan2(rotation.w)
+}
+
+/// Wrap an angle into [-PI, PI)
+fn wrap_angle(angle: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+    (angle + PI).rem_euclid(TAU) - PI
+}
@@ -0,0 +1,195 @@
+//! Evaluating keyframes at arbitrary times the way Roblox's Animator does
+//!
+//! Each bone interpolates between its own surrounding poses, whichever keyframes they are
+//! in. The earlier pose's easing shapes the segment, and a bone holds its first pose before
+//! it and its last pose after it.
+//!
+//! The legacy `Cubic`, `Elastic` and `Bounce` pose styles apply their direction the other
+//! way around from TweenService: a pose eased `In` decelerates into the next pose. `CubicV2`
+//! uses the TweenService convention.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use glam::{Quat, Vec3};
+use rbx_types::CFrame;
+
+use crate::types::{EasingDirection, EasingStyle, Keyframe, Pose};
+use crate::utils::{cframe_from_glam, cframe_to_glam, quat_angle_between};
+
+/// Map linear progress through a segment to eased progress
+///
+/// `alpha` runs from 0 at the segment's first pose to 1 at its last. Elastic easing
+/// overshoots outside that range.
+pub fn ease(style: EasingStyle, direction: EasingDirection, alpha: f32) -> f32 {
+    let alpha = alpha.clamp(0.0, 1.0);
+    let curve: fn(f32) -> f32 = match style {
+        EasingStyle::Linear => return alpha,
+        EasingStyle::Constant => return if alpha < 1.0 { 0.0 } else { 1.0 },
+        EasingStyle::Cubic | EasingStyle::CubicV2 => |a| a * a * a,
+        EasingStyle::Elastic => elastic_in,
+        EasingStyle::Bounce => |a| 1.0 - bounce_out(1.0 - a),
+    };
+    let direction = match style {
+        EasingStyle::CubicV2 => direction,
+        _ => direction.reversed(),
+    };
+
+    match direction {
+        EasingDirection::In => curve(alpha),
+        EasingDirection::Out => 1.0 - curve(1.0 - alpha),
+        EasingDirection::InOut => {
+            if alpha < 0.5 {
+                curve(alpha * 2.0) / 2.0
+            } else {
+                1.0 - curve((1.0 - alpha) * 2.0) / 2.0
+            }
+        }
+    }
+}
+
+fn elastic_in(alpha: f32) -> f32 {
+    if alpha <= 0.0 || alpha >= 1.0 {
+        return alpha;
+    }
+    let period = 0.3;
+    -(2.0_f32.powf(10.0 * (alpha - 1.0))) * ((alpha - 1.0 - period / 4.0) * 2.0 * PI / period).sin()
+}
+
+fn bounce_out(alpha: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if alpha < 1.0 / D {
+        N * alpha * alpha
+    } else if alpha < 2.0 / D {
+        let a = alpha - 1.5 / D;
+        N * a * a + 0.75
+    } else if alpha < 2.5 / D {
+        let a = alpha - 2.25 / D;
+        N * a * a + 0.9375
+    } else {
+        let a = alpha - 2.625 / D;
+        N * a * a + 0.984375
+    }
+}
+
+/// Keyframes prepared for repeated sampling
+#[derive(Debug, Clone, Default)]
+pub struct SequenceSampler {
+    tracks: HashMap<String, Vec<(f64, Pose)>>,
+}
+
+impl SequenceSampler {
+    /// Collect each bone's poses in time order
+    pub fn new(keyframes: &[Keyframe]) -> Self {
+        let mut tracks: HashMap<String, Vec<(f64, Pose)>> = HashMap::new();
+        for keyframe in keyframes {
+            for pose in &keyframe.poses {
+                tracks
+                    .entry(pose.name.clone())
+                    .or_default()
+                    .push((keyframe.time, pose.clone()));
+            }
+        }
+        for track in tracks.values_mut() {
+            track.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+        Self { tracks }
+    }
+
+    /// The names of the posed bones
+    pub fn bones(&self) -> impl Iterator<Item = &str> {
+        self.tracks.keys().map(String::as_str)
+    }
+
+    /// A bone's pose at `time` seconds, or `None` if it has no poses
+    pub fn sample_bone(&self, name: &str, time: f64) -> Option<CFrame> {
+        self.sample_bone_glam(name, time)
+            .map(|(position, rotation)| cframe_from_glam(position, rotation))
+    }
+
+    /// A bone's pose at `time` seconds as a translation and rotation
+    pub fn sample_bone_glam(&self, name: &str, time: f64) -> Option<(Vec3, Quat)> {
+        let track = self.tracks.get(name)?;
+        let next = track.partition_point(|(key_time, _)| *key_time <= time);
+        if next == 0 {
+            return Some(cframe_to_glam(&track[0].1.cframe));
+        }
+        let (start_time, start) = &track[next - 1];
+        let Some((end_time, end)) = track.get(next) else {
+            return Some(cframe_to_glam(&start.cframe));
+        };
+
+        let alpha = ((time - start_time) / (end_time - start_time)) as f32;
+        let alpha = ease(start.easing_style, start.easing_direction, alpha);
+        let (start_position, start_rotation) = cframe_to_glam(&start.cframe);
+        let (end_position, end_rotation) = cframe_to_glam(&end.cframe);
+        Some((
+            start_position.lerp(end_position, alpha),
+            start_rotation.slerp(end_rotation, alpha).normalize(),
+        ))
+    }
+
+    /// Every posed bone's pose at `time` seconds
+    pub fn sample(&self, time: f64) -> HashMap<String, CFrame> {
+        self.tracks
+            .keys()
+            .filter_map(|name| Some((name.clone(), self.sample_bone(name, time)?)))
+            .collect()
+    }
+}
+
+/// Every posed bone's pose at `time` seconds; see [`SequenceSampler`]
+pub fn sample_keyframes(keyframes: &[Keyframe], time: f64) -> HashMap<String, CFrame> {
+    SequenceSampler::new(keyframes).sample(time)
+}
+
+/// The largest position (studs) and rotation (degrees) difference between two sequences
+///
+/// Both are sampled at `sample_rate` samples per second across their combined length, and
+/// at every keyframe time. Bones posed in only one sequence count against the rest pose.
+pub fn max_deviation(
+    reference: &[Keyframe],
+    candidate: &[Keyframe],
+    sample_rate: f64,
+) -> (f32, f32) {
+    let reference_sampler = SequenceSampler::new(reference);
+    let candidate_sampler = SequenceSampler::new(candidate);
+    let mut bones: Vec<&str> = reference_sampler
+        .bones()
+        .chain(candidate_sampler.bones())
+        .collect();
+    bones.sort_unstable();
+    bones.dedup();
+
+    let mut times: Vec<f64> = reference
+        .iter()
+        .chain(candidate)
+        .map(|kf| kf.time)
+        .collect();
+    if let (Some(start), Some(end)) = (
+        times.iter().copied().min_by(f64::total_cmp),
+        times.iter().copied().max_by(f64::total_cmp),
+    ) && sample_rate > 0.0
+    {
+        let steps = ((end - start) * sample_rate).ceil() as usize;
+        times.extend((0..=steps).map(|step| start + step as f64 / sample_rate));
+    }
+
+    let rest = (Vec3::ZERO, Quat::IDENTITY);
+    let mut max_position = 0.0_f32;
+    let mut max_angle = 0.0_f32;
+    for &time in &times {
+        for &bone in &bones {
+            let (a_position, a_rotation) = reference_sampler
+                .sample_bone_glam(bone, time)
+                .unwrap_or(rest);
+            let (b_position, b_rotation) = candidate_sampler
+                .sample_bone_glam(bone, time)
+                .unwrap_or(rest);
+            max_position = max_position.max(a_position.distance(b_position));
+            max_angle = max_angle.max(quat_angle_between(a_rotation, b_rotation).to_degrees());
+        }
+    }
+    (max_position, max_angle)
+}
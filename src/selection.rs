@@ -0,0 +1,122 @@
+//! Bone selection through include and exclude patterns
+//!
+//! Patterns are globs (`*`, `?` and `[...]`) matched against the whole bone name, or
+//! regular expressions when prefixed with `re:`. Include patterns select whole subtrees: when any
+//! are given, only bones matching one, or descending from a bone that does, are kept.
+//! Exclude patterns drop just the bones they match, and win over include patterns.
+//!
+//! Children of a dropped bone are reparented to their nearest kept ancestor, with the
+//! dropped bones' rest transforms and animation composed into their own.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use log::debug;
+use regex::Regex;
+
+use crate::types::{Keyframe, NodeInfo};
+use crate::utils::{collapse_bone_infos, collapse_bone_keyframes};
+
+/// A compiled set of include and exclude patterns
+#[derive(Debug, Clone, Default)]
+pub struct BoneSelection {
+    include: Vec<BonePattern>,
+    exclude: Vec<BonePattern>,
+}
+
+impl BoneSelection {
+    /// Create a selection that keeps every bone
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a pattern selecting subtrees to keep
+    pub fn include(mut self, pattern: &str) -> Result<Self> {
+        self.include.push(BonePattern::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Add a pattern selecting bones to drop
+    pub fn exclude(mut self, pattern: &str) -> Result<Self> {
+        self.exclude.push(BonePattern::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Whether the selection keeps every bone
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether a bone is kept, given the bone hierarchy it belongs to
+    pub fn keeps(&self, name: &str, bone_infos: &HashMap<String, NodeInfo>) -> bool {
+        if self.exclude.iter().any(|pattern| pattern.matches(name)) {
+            return false;
+        }
+        if self.include.is_empty() {
+            return true;
+        }
+
+        let mut current = Some(name);
+        while let Some(name) = current {
+            if self.include.iter().any(|pattern| pattern.matches(name)) {
+                return true;
+            }
+            current = bone_infos.get(name).and_then(|info| info.parent.as_deref());
+        }
+        false
+    }
+
+    /// Remove unselected bones from a hierarchy
+    ///
+    /// Kept bones are attached to their nearest kept ancestor, with the rest transforms
+    /// of the removed bones in between composed into their own.
+    pub fn apply_to_bone_infos(
+        &self,
+        bone_infos: &HashMap<String, NodeInfo>,
+    ) -> HashMap<String, NodeInfo> {
+        collapse_bone_infos(bone_infos, &|name| self.keeps(name, bone_infos))
+    }
+
+    /// Remove the poses of unselected bones, baking their motion into kept descendants
+    ///
+    /// `bone_infos` is the unfiltered source hierarchy. Returns the names of the removed
+    /// bones that had poses, sorted.
+    pub fn apply_to_keyframes(
+        &self,
+        keyframes: &mut Vec<Keyframe>,
+        bone_infos: &HashMap<String, NodeInfo>,
+    ) -> Vec<String> {
+        let removed =
+            collapse_bone_keyframes(keyframes, bone_infos, &|name| self.keeps(name, bone_infos));
+        for name in &removed {
+            debug!("Excluded bone {}", name);
+        }
+        removed
+    }
+}
+
+/// A glob, or a regex when prefixed with `re:`
+#[derive(Debug, Clone)]
+enum BonePattern {
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl BonePattern {
+    fn new(pattern: &str) -> Result<Self> {
+        let context = || format!("Invalid bone pattern '{}'", pattern);
+        match pattern.strip_prefix("re:") {
+            Some(regex) => Regex::new(regex).map(Self::Regex).with_context(context),
+            None => glob::Pattern::new(pattern)
+                .map(Self::Glob)
+                .with_context(context),
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Glob(glob) => glob.matches(name),
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
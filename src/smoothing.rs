@@ -0,0 +1,178 @@
+//! Noise smoothing and spike repair for captured animation
+//!
+//! Each bone's track is filtered on its own poses, in time order. Spikes are single poses
+//! that leave the line through their neighbours while the neighbours stay close together;
+//! they are replaced by the interpolation of the neighbours. Smoothing then applies a
+//! Gaussian window, averaging rotations as hemisphere-aligned quaternions.
+
+use std::collections::HashMap;
+
+use glam::{Quat, Vec3, Vec4};
+
+use crate::types::{Keyframe, Tolerance};
+use crate::utils::{cframe_from_glam, cframe_to_glam, quat_angle_between};
+
+/// Smoothing and spike repair settings
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SmoothingConfig {
+    /// Gaussian standard deviation in poses, or `0` to skip smoothing
+    pub strength: f32,
+    /// Per-bone strengths overriding [`Self::strength`]
+    pub bone_strengths: HashMap<String, f32>,
+    /// How far a pose may leave the line through its neighbours before it counts as a
+    /// spike, or `None` to skip spike repair
+    pub spike_tolerance: Option<Tolerance>,
+}
+
+impl SmoothingConfig {
+    /// Create a config that smooths every bone with the given strength
+    pub fn new(strength: f32) -> Self {
+        Self {
+            strength,
+            ..Default::default()
+        }
+    }
+
+    /// The smoothing strength used for a bone
+    pub fn strength_for(&self, bone: &str) -> f32 {
+        self.bone_strengths
+            .get(bone)
+            .copied()
+            .unwrap_or(self.strength)
+    }
+}
+
+/// A pose replaced by spike repair
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepairedSpike {
+    /// The bone the spike was on
+    pub bone: String,
+    /// Time of the spike in seconds
+    pub time: f64,
+}
+
+/// Summary of the smoothing stage
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SmoothingReport {
+    /// Number of bones whose tracks were smoothed
+    pub smoothed_bones: usize,
+    /// Repaired spikes, by bone name and then time
+    pub spikes: Vec<RepairedSpike>,
+}
+
+/// Repair spikes and smooth every bone track
+pub fn smooth_keyframes(keyframes: &mut [Keyframe], config: &SmoothingConfig) -> SmoothingReport {
+    // Each bone's poses as (keyframe index, pose index), in time order
+    let mut tracks: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+    let mut order: Vec<usize> = (0..keyframes.len()).collect();
+    order.sort_by(|&a, &b| keyframes[a].time.total_cmp(&keyframes[b].time));
+    for keyframe_index in order {
+        for (pose_index, pose) in keyframes[keyframe_index].poses.iter().enumerate() {
+            tracks
+                .entry(pose.name.clone())
+                .or_default()
+                .push((keyframe_index, pose_index));
+        }
+    }
+
+    let mut names: Vec<&String> = tracks.keys().collect();
+    names.sort();
+
+    let mut report = SmoothingReport::default();
+    for name in names {
+        let indices = &tracks[name];
+        let times: Vec<f64> = indices.iter().map(|&(kf, _)| keyframes[kf].time).collect();
+        let mut samples: Vec<(Vec3, Quat)> = indices
+            .iter()
+            .map(|&(kf, pose)| cframe_to_glam(&keyframes[kf].poses[pose].cframe))
+            .collect();
+
+        if let Some(tolerance) = config.spike_tolerance {
+            for index in repair_spikes(&times, &mut samples, tolerance) {
+                report.spikes.push(RepairedSpike {
+                    bone: name.clone(),
+                    time: times[index],
+                });
+            }
+        }
+
+        let strength = config.strength_for(name);
+        if strength > 0.0 && samples.len() > 2 {
+            samples = gaussian_smooth(&samples, strength);
+            report.smoothed_bones += 1;
+        }
+
+        for (&(kf, pose), &(position, rotation)) in indices.iter().zip(&samples) {
+            keyframes[kf].poses[pose].cframe = cframe_from_glam(position, rotation);
+        }
+    }
+
+    report
+}
+
+/// Replace poses that stick out from their neighbours; returns the replaced indices
+fn repair_spikes(times: &[f64], samples: &mut [(Vec3, Quat)], tolerance: Tolerance) -> Vec<usize> {
+    let mut repaired = Vec::new();
+    for index in 1..samples.len().saturating_sub(1) {
+        let (previous, current, next) = (samples[index - 1], samples[index], samples[index + 1]);
+        let span = times[index + 1] - times[index - 1];
+        let t = if span > 0.0 {
+            ((times[index] - times[index - 1]) / span) as f32
+        } else {
+            0.5
+        };
+        let expected = (
+            previous.0.lerp(next.0, t),
+            previous.1.slerp(next.1, t).normalize(),
+        );
+
+        let position_error = current.0.distance(expected.0);
+        let angle_error = quat_angle_between(current.1, expected.1).to_degrees();
+        let neighbour_position = previous.0.distance(next.0);
+        let neighbour_angle = quat_angle_between(previous.1, next.1).to_degrees();
+
+        // Fast but smooth motion moves the neighbours apart as well, so only count poses
+        // that stray further than the neighbours are from each other
+        let position_spike =
+            position_error > tolerance.position && position_error > neighbour_position;
+        let angle_spike = angle_error > tolerance.angle && angle_error > neighbour_angle;
+        if position_spike || angle_spike {
+            samples[index] = expected;
+            repaired.push(index);
+        }
+    }
+    repaired
+}
+
+/// Gaussian-weighted average over neighbouring poses, renormalized at the track ends
+fn gaussian_smooth(samples: &[(Vec3, Quat)], sigma: f32) -> Vec<(Vec3, Quat)> {
+    let radius = (sigma * 3.0).ceil() as usize;
+    let weights: Vec<f32> = (0..=radius)
+        .map(|offset| (-((offset * offset) as f32) / (2.0 * sigma * sigma)).exp())
+        .collect();
+
+    (0..samples.len())
+        .map(|index| {
+            let center = samples[index].1;
+            let mut position = Vec3::ZERO;
+            let mut rotation = Vec4::ZERO;
+            let mut total = 0.0;
+
+            let first = index.saturating_sub(radius);
+            let last = (index + radius).min(samples.len() - 1);
+            for (neighbour, &(p, q)) in samples.iter().enumerate().take(last + 1).skip(first) {
+                let weight = weights[neighbour.abs_diff(index)];
+                // q and -q are the same rotation; average on the center's hemisphere
+                let q = if q.dot(center) < 0.0 { -q } else { q };
+                position += p * weight;
+                rotation += Vec4::from(q) * weight;
+                total += weight;
+            }
+
+            (
+                position / total,
+                Quat::from_vec4(rotation / total).normalize(),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,179 @@
+//! Time operations on keyframes: scaling, stretching, reversing and ping-pong
+//!
+//! A pose's easing describes the segment from it to the bone's next pose. Reversing a clip
+//! turns each segment around, so its easing moves to the pose at the other end and `In`
+//! and `Out` swap. `Constant` segments also move the value they hold, see [`reverse`].
+
+use std::collections::HashMap;
+
+use crate::types::{EasingStyle, Keyframe, Pose};
+
+/// How long after a reversed `Constant` segment starts its held value is baked, in seconds
+pub const CONSTANT_BAKE_DELAY: f64 = 0.001;
+
+/// A time operation applied to a whole clip
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TimeOperation {
+    /// Multiply every keyframe time by a positive, finite factor; `2.0` plays at half speed
+    Scale(f64),
+    /// Scale the clip so its last keyframe lands at this many seconds, which must be positive
+    Stretch(f64),
+    /// Play the clip backwards
+    Reverse,
+    /// Play the clip forwards, then backwards
+    PingPong,
+}
+
+impl TimeOperation {
+    /// Apply the operation to keyframes, leaving them sorted by time
+    ///
+    /// # Panics
+    ///
+    /// Panics if a [`Self::Scale`] factor or [`Self::Stretch`] duration is not positive and
+    /// finite.
+    pub fn apply(self, keyframes: &mut Vec<Keyframe>) {
+        match self {
+            Self::Scale(factor) => scale_time(keyframes, factor),
+            Self::Stretch(duration) => stretch_to_duration(keyframes, duration),
+            Self::Reverse => reverse(keyframes),
+            Self::PingPong => ping_pong(keyframes),
+        }
+    }
+}
+
+/// Multiply every keyframe time by `factor`
+///
+/// # Panics
+///
+/// Panics if `factor` is not a positive, finite number.
+pub fn scale_time(keyframes: &mut [Keyframe], factor: f64) {
+    assert!(
+        factor > 0.0 && factor.is_finite(),
+        "time scale factor must be positive and finite, got {}",
+        factor
+    );
+    for keyframe in keyframes.iter_mut() {
+        keyframe.time *= factor;
+    }
+    sort_by_time(keyframes);
+}
+
+/// Scale keyframe times so the last keyframe lands at `duration` seconds
+///
+/// Clips that end at time zero are left unchanged.
+///
+/// # Panics
+///
+/// Panics if `duration` is not a positive, finite number.
+pub fn stretch_to_duration(keyframes: &mut [Keyframe], duration: f64) {
+    assert!(
+        duration > 0.0 && duration.is_finite(),
+        "stretched duration must be positive and finite, got {}",
+        duration
+    );
+    let end = end_time(keyframes);
+    if end > 0.0 {
+        scale_time(keyframes, duration / end);
+    }
+}
+
+/// Play keyframes backwards over the same time span
+///
+/// A `Constant` segment holds the pose it starts from, which becomes the segment's last
+/// pose, so the held value moves to the pose that now starts the segment. When that pose
+/// also ends an eased segment it keeps its own value, and the held value is baked into an
+/// extra pose [`CONSTANT_BAKE_DELAY`] later.
+pub fn reverse(keyframes: &mut Vec<Keyframe>) {
+    let Some((start, end)) = time_span(keyframes) else {
+        return;
+    };
+
+    let mut tracks: HashMap<String, Vec<(f64, Pose)>> = HashMap::new();
+    for keyframe in keyframes.iter() {
+        for pose in &keyframe.poses {
+            tracks
+                .entry(pose.name.clone())
+                .or_default()
+                .push((keyframe.time, pose.clone()));
+        }
+    }
+    for track in tracks.values_mut() {
+        track.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+
+    let mut baked = Vec::new();
+    for keyframe in keyframes.iter_mut() {
+        let time = keyframe.time;
+        keyframe.time = start + end - time;
+        for pose in &mut keyframe.poses {
+            let track = &tracks[&pose.name];
+            let index = track.partition_point(|(t, _)| *t < time);
+
+            // Each segment's easing lives on its first pose, which becomes the segment's last
+            let (previous_time, previous) = &track[index.saturating_sub(1)];
+            let incoming = pose.easing_style;
+            pose.easing_style = previous.easing_style;
+            pose.easing_direction = previous.easing_direction.reversed();
+            if index == 0 || previous.easing_style != EasingStyle::Constant {
+                continue;
+            }
+
+            if index + 1 == track.len() || incoming == EasingStyle::Constant {
+                pose.cframe = previous.cframe;
+            } else {
+                let delay = CONSTANT_BAKE_DELAY.min((time - previous_time) / 2.0);
+                let held = Pose {
+                    cframe: previous.cframe,
+                    ..pose.clone()
+                };
+                baked.push((keyframe.time + delay, held));
+            }
+        }
+    }
+
+    for (time, pose) in baked {
+        match keyframes.iter_mut().find(|keyframe| keyframe.time == time) {
+            Some(keyframe) => keyframe.poses.push(pose),
+            None => keyframes.push(Keyframe {
+                time,
+                poses: vec![pose],
+            }),
+        }
+    }
+    sort_by_time(keyframes);
+}
+
+/// Play keyframes forwards, then backwards, doubling the clip's length
+pub fn ping_pong(keyframes: &mut Vec<Keyframe>) {
+    let Some((start, end)) = time_span(keyframes) else {
+        return;
+    };
+
+    sort_by_time(keyframes);
+    let mut backwards = keyframes.clone();
+    reverse(&mut backwards);
+    for keyframe in &mut backwards {
+        keyframe.time += end - start;
+    }
+
+    // The turnaround keyframe comes from the reversed half, which eases into the return
+    keyframes.pop();
+    keyframes.extend(backwards);
+}
+
+fn time_span(keyframes: &[Keyframe]) -> Option<(f64, f64)> {
+    let start = keyframes.iter().map(|kf| kf.time).min_by(f64::total_cmp)?;
+    Some((start, end_time(keyframes)))
+}
+
+fn end_time(keyframes: &[Keyframe]) -> f64 {
+    keyframes
+        .iter()
+        .map(|kf| kf.time)
+        .max_by(f64::total_cmp)
+        .unwrap_or(0.0)
+}
+
+fn sort_by_time(keyframes: &mut [Keyframe]) {
+    keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+}
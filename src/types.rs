@@ -13,12 +13,99 @@ pub struct NodeInfo {
 }
 
 /// A pose for a specific bone at a specific time
+///
+/// Build poses with [`Pose::new`] and [`Pose::with_easing`]; more fields may be added.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Pose {
     /// The name of the bone this pose applies to
     pub name: String,
     /// The CFrame transformation for this pose
     pub cframe: CFrame,
+    /// How the animation eases from this pose into the bone's next pose
+    pub easing_style: EasingStyle,
+    /// Which end of the segment after this pose the easing style applies to
+    pub easing_direction: EasingDirection,
+}
+
+impl Pose {
+    /// Create a pose that interpolates linearly into the next one
+    pub fn new(name: impl Into<String>, cframe: CFrame) -> Self {
+        Self {
+            name: name.into(),
+            cframe,
+            easing_style: EasingStyle::Linear,
+            easing_direction: EasingDirection::In,
+        }
+    }
+
+    /// Builder method to set how the animation eases from this pose into the next one
+    pub fn with_easing(mut self, style: EasingStyle, direction: EasingDirection) -> Self {
+        self.easing_style = style;
+        self.easing_direction = direction;
+        self
+    }
+}
+
+/// Roblox `PoseEasingStyle`: the curve used between a pose and the next one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EasingStyle {
+    /// Interpolate at a constant rate
+    #[default]
+    Linear,
+    /// Hold the pose until the next one
+    Constant,
+    /// Overshoot the next pose and spring back to it
+    Elastic,
+    /// Legacy cubic curve; Roblox applies its direction backwards, so `In` eases out
+    Cubic,
+    /// Bounce against the next pose before settling on it
+    Bounce,
+    /// Cubic curve that fixes the reversed direction of [`Self::Cubic`]
+    CubicV2,
+}
+
+impl EasingStyle {
+    /// The Roblox enum value
+    pub fn value(self) -> u32 {
+        match self {
+            Self::Linear => 0,
+            Self::Constant => 1,
+            Self::Elastic => 2,
+            Self::Cubic => 3,
+            Self::Bounce => 4,
+            Self::CubicV2 => 5,
+        }
+    }
+}
+
+/// Roblox `PoseEasingDirection`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EasingDirection {
+    #[default]
+    In,
+    Out,
+    InOut,
+}
+
+impl EasingDirection {
+    /// The Roblox enum value
+    pub fn value(self) -> u32 {
+        match self {
+            Self::In => 0,
+            Self::Out => 1,
+            Self::InOut => 2,
+        }
+    }
+
+    /// The direction that traces the same curve when time runs backwards
+    pub fn reversed(self) -> Self {
+        match self {
+            Self::In => Self::Out,
+            Self::Out => Self::In,
+            Self::InOut => Self::InOut,
+        }
+    }
 }
 
 /// A keyframe containing poses for multiple bones at a specific time
@@ -30,13 +117,58 @@ pub struct Keyframe {
     pub poses: Vec<Pose>,
 }
 
+/// Error bounds for comparing poses in physical units
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Maximum translation difference in studs
+    pub position: f32,
+    /// Maximum rotation difference in degrees
+    pub angle: f32,
+}
+
+impl Tolerance {
+    /// Create a new tolerance from a translation (studs) and angle (degrees) bound
+    pub fn new(position: f32, angle: f32) -> Self {
+        Self { position, angle }
+    }
+
+    /// How far the given errors exceed this tolerance
+    ///
+    /// Returns the larger of the two error-to-bound ratios, so values above 1 mean
+    /// the errors are out of tolerance. A zero bound only accepts zero error.
+    pub fn exceedance(&self, position_error: f32, angle_error: f32) -> f32 {
+        fn ratio(error: f32, bound: f32) -> f32 {
+            if bound > 0.0 {
+                error / bound
+            } else if error > 0.0 {
+                f32::INFINITY
+            } else {
+                0.0
+            }
+        }
+
+        ratio(position_error, self.position).max(ratio(angle_error, self.angle))
+    }
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            position: 0.01,
+            angle: 0.5,
+        }
+    }
+}
+
 /// Configuration options for animation conversion
 #[derive(Debug, Clone)]
 pub struct ConversionConfig {
     /// Whether to filter out bones with identical poses across all keyframes
     pub filter_identical_bones: bool,
-    /// Epsilon value for floating-point comparisons
+    /// Translation tolerance in studs for pose comparisons
     pub epsilon: f32,
+    /// Rotation tolerance in degrees for pose comparisons
+    pub angle_tolerance: f32,
     /// Default ticks per second if not specified in the animation
     pub default_ticks_per_second: f64,
 }
@@ -46,6 +178,7 @@ impl Default for ConversionConfig {
         Self {
             filter_identical_bones: true,
             epsilon: 1e-5,
+            angle_tolerance: 1e-3,
             default_ticks_per_second: 24.0,
         }
     }
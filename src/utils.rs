@@ -1,16 +1,62 @@
 //! Utility functions for working with Assimp scenes and bone hierarchies
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
+use glam::{Mat3, Mat4, Quat, Vec3, Vec4};
 use log::debug;
-use rbx_types::{Matrix3, Vector3};
-use russimp::{node::Node, scene::Scene};
+use rbx_types::{CFrame, Matrix3, Vector3};
+use russimp::{Matrix4x4, node::Node, scene::Scene};
 
-use crate::types::NodeInfo;
+use crate::types::{Keyframe, NodeInfo, Pose, Tolerance};
+
+/// Separator Assimp puts between a bone name and the suffix of its FBX pivot helper nodes
+const FBX_HELPER_MARKER: &str = "_$AssimpFbx$_";
+
+/// The bone owning an Assimp FBX pivot helper such as `Arm_$AssimpFbx$_Rotation`
+pub fn fbx_helper_owner(name: &str) -> Option<&str> {
+    name.find(FBX_HELPER_MARKER).map(|index| &name[..index])
+}
+
+/// Remove FBX pivot helpers from a hierarchy, composing their rest transforms into their owners
+pub fn fold_fbx_helper_infos(bone_infos: &HashMap<String, NodeInfo>) -> HashMap<String, NodeInfo> {
+    collapse_bone_infos(bone_infos, &|name| fbx_helper_owner(name).is_none())
+}
+
+/// Fold the poses of FBX pivot helpers into their owning bones
+///
+/// `bone_infos` is the hierarchy including the helpers. Returns the folded helper names.
+pub fn fold_fbx_helpers(
+    keyframes: &mut Vec<Keyframe>,
+    bone_infos: &HashMap<String, NodeInfo>,
+) -> Vec<String> {
+    let folded = collapse_bone_keyframes(keyframes, bone_infos, &|name| {
+        fbx_helper_owner(name).is_none()
+    });
+    for name in &folded {
+        debug!("Folded FBX helper {}", name);
+    }
+    folded
+}
 
 /// Extract node information from an Assimp scene
+///
+/// Only animated nodes, and the owners of animated FBX pivot helpers, are recorded. The
+/// transforms of unanimated nodes between a bone and its nearest animated ancestor (or the
+/// scene root) are composed into the bone's rest transform, so every rest transform is
+/// expressed in its recorded parent's space.
 pub fn get_bone_infos(scene: &Scene) -> HashMap<String, NodeInfo> {
+    get_bone_infos_with_offsets(scene).0
+}
+
+/// Extract node information along with each bone's parent offset
+///
+/// The offset is the composed transform of the unanimated nodes above the bone, mapping
+/// its node-local parent space into the space of its recorded parent.
+pub fn get_bone_infos_with_offsets(
+    scene: &Scene,
+) -> (HashMap<String, NodeInfo>, HashMap<String, Mat4>) {
     let mut bone_infos = HashMap::new();
+    let mut offsets = HashMap::new();
 
     // First, collect all node names that have animation channels
     let mut animated_channels = HashSet::new();
@@ -26,42 +72,67 @@ pub fn get_bone_infos(scene: &Scene) -> HashMap<String, NodeInfo> {
         animated_channels
     );
 
+    // Bones whose animation Assimp moved onto FBX pivot helpers are bones too
+    let helper_owners: Vec<String> = animated_channels
+        .iter()
+        .filter_map(|name| fbx_helper_owner(name).map(str::to_owned))
+        .collect();
+    animated_channels.extend(helper_owners);
+
     if let Some(root) = &scene.root {
-        collect_node_bone_infos(root, None, &mut bone_infos, &animated_channels);
+        collect_node_bone_infos(
+            root,
+            None,
+            Mat4::IDENTITY,
+            &mut bone_infos,
+            &mut offsets,
+            &animated_channels,
+        );
     }
 
-    bone_infos
+    (bone_infos, offsets)
 }
 
 fn collect_node_bone_infos(
     node: &Node,
     parent: Option<String>,
+    offset: Mat4,
     transforms: &mut HashMap<String, NodeInfo>,
+    offsets: &mut HashMap<String, Mat4>,
     animated_channels: &HashSet<String>,
 ) {
-    // A node is a bone if it has animation channels
+    // A node is a bone if it has animation channels or owns animated helpers
     let is_bone = animated_channels.contains(&node.name);
+    let local = matrix4x4_to_mat4(&node.transformation);
 
     if is_bone {
-        // Store this node's rest transform
+        // Store this node's rest transform in its animated parent's space
         transforms.insert(
             node.name.clone(),
             NodeInfo {
-                rest_transform: node.transformation,
+                rest_transform: mat4_to_matrix4x4(&(offset * local)),
                 parent: parent.clone(),
             },
         );
+        offsets.insert(node.name.clone(), offset);
         debug!("Added BONE: {} (parent: {:?})", node.name, parent);
     }
 
     // Recursively collect children, passing current node as parent only if it's a bone
-    let next_parent = if is_bone {
-        Some(node.name.clone())
+    let (next_parent, next_offset) = if is_bone {
+        (Some(node.name.clone()), Mat4::IDENTITY)
     } else {
-        parent
+        (parent, offset * local)
     };
     for child in node.children.borrow().iter() {
-        collect_node_bone_infos(child, next_parent.clone(), transforms, animated_channels);
+        collect_node_bone_infos(
+            child,
+            next_parent.clone(),
+            next_offset,
+            transforms,
+            offsets,
+            animated_channels,
+        );
     }
 }
 
@@ -78,7 +149,291 @@ pub fn approx_equal_matrix3(a: &Matrix3, b: &Matrix3, epsilon: f32) -> bool {
 }
 
 /// Check if two CFrame values are approximately equal
-pub fn approx_equal_cframe(a: &rbx_types::CFrame, b: &rbx_types::CFrame, epsilon: f32) -> bool {
+pub fn approx_equal_cframe(a: &CFrame, b: &CFrame, epsilon: f32) -> bool {
     approx_equal_vec3(&a.position, &b.position, epsilon)
         && approx_equal_matrix3(&a.orientation, &b.orientation, epsilon)
 }
+
+/// Angle in radians of the relative rotation between two quaternions
+///
+/// Uses `atan2` rather than `acos` so tiny angles keep their precision.
+pub fn quat_angle_between(a: Quat, b: Quat) -> f32 {
+    let relative = a.inverse() * b;
+    2.0 * relative.xyz().length().atan2(relative.w.abs())
+}
+
+/// Angle in degrees of the relative rotation between two CFrames
+pub fn rotation_angle_between(a: &CFrame, b: &CFrame) -> f32 {
+    let (_, a_rotation) = cframe_to_glam(a);
+    let (_, b_rotation) = cframe_to_glam(b);
+    quat_angle_between(a_rotation, b_rotation).to_degrees()
+}
+
+/// Check if two CFrame values are within a translation (studs) and angular (degrees) tolerance
+pub fn cframe_within_tolerance(a: &CFrame, b: &CFrame, tolerance: Tolerance) -> bool {
+    let a_position = Vec3::new(a.position.x, a.position.y, a.position.z);
+    let b_position = Vec3::new(b.position.x, b.position.y, b.position.z);
+    a_position.distance(b_position) <= tolerance.position
+        && rotation_angle_between(a, b) <= tolerance.angle
+}
+
+/// Split a CFrame into its glam position and rotation
+pub fn cframe_to_glam(cframe: &CFrame) -> (Vec3, Quat) {
+    let position = Vec3::new(cframe.position.x, cframe.position.y, cframe.position.z);
+    let orientation = &cframe.orientation;
+    let rotation = Quat::from_mat3(&Mat3::from_cols(
+        Vec3::new(orientation.x.x, orientation.x.y, orientation.x.z),
+        Vec3::new(orientation.y.x, orientation.y.y, orientation.y.z),
+        Vec3::new(orientation.z.x, orientation.z.y, orientation.z.z),
+    ));
+    (position, rotation.normalize())
+}
+
+/// Build a CFrame from a glam position and rotation
+pub fn cframe_from_glam(position: Vec3, rotation: Quat) -> CFrame {
+    let from_glam = Mat3::from_quat(rotation);
+    CFrame::new(
+        Vector3::new(position.x, position.y, position.z),
+        Matrix3 {
+            x: Vector3::new(from_glam.x_axis.x, from_glam.x_axis.y, from_glam.x_axis.z),
+            y: Vector3::new(from_glam.y_axis.x, from_glam.y_axis.y, from_glam.y_axis.z),
+            z: Vector3::new(from_glam.z_axis.x, from_glam.z_axis.y, from_glam.z_axis.z),
+        },
+    )
+}
+
+/// Convert an Assimp matrix to a glam matrix
+pub fn matrix4x4_to_mat4(m: &Matrix4x4) -> Mat4 {
+    Mat4::from_cols(
+        Vec4::new(m.a1, m.b1, m.c1, m.d1),
+        Vec4::new(m.a2, m.b2, m.c2, m.d2),
+        Vec4::new(m.a3, m.b3, m.c3, m.d3),
+        Vec4::new(m.a4, m.b4, m.c4, m.d4),
+    )
+}
+
+/// Convert a glam matrix to an Assimp matrix
+pub fn mat4_to_matrix4x4(m: &Mat4) -> Matrix4x4 {
+    Matrix4x4 {
+        a1: m.x_axis.x,
+        a2: m.y_axis.x,
+        a3: m.z_axis.x,
+        a4: m.w_axis.x,
+        b1: m.x_axis.y,
+        b2: m.y_axis.y,
+        b3: m.z_axis.y,
+        b4: m.w_axis.y,
+        c1: m.x_axis.z,
+        c2: m.y_axis.z,
+        c3: m.z_axis.z,
+        c4: m.w_axis.z,
+        d1: m.x_axis.w,
+        d2: m.y_axis.w,
+        d3: m.z_axis.w,
+        d4: m.w_axis.w,
+    }
+}
+
+/// Split an Assimp matrix into translation and rotation, discarding scale
+pub fn decompose_matrix4x4(m: &Matrix4x4) -> (Vec3, Quat) {
+    let (_, rotation, translation) = matrix4x4_to_mat4(m).to_scale_rotation_translation();
+    (translation, rotation)
+}
+
+/// A bone's rest-relative poses as (time, position, rotation), ordered by time
+pub(crate) type PoseTrack = Vec<(f64, Vec3, Quat)>;
+
+/// Collect each bone's poses into a track ordered by time
+pub(crate) fn collect_pose_tracks(keyframes: &[Keyframe]) -> HashMap<&str, PoseTrack> {
+    let mut tracks: HashMap<&str, PoseTrack> = HashMap::new();
+    for keyframe in keyframes {
+        for pose in &keyframe.poses {
+            let (position, rotation) = cframe_to_glam(&pose.cframe);
+            tracks
+                .entry(pose.name.as_str())
+                .or_default()
+                .push((keyframe.time, position, rotation));
+        }
+    }
+    for track in tracks.values_mut() {
+        track.sort_by(|a, b| a.0.total_cmp(&b.0));
+    }
+    tracks
+}
+
+/// Linearly interpolate a track, holding its first and last values outside its range
+pub(crate) fn sample_pose_track(track: &PoseTrack, time: f64) -> (Vec3, Quat) {
+    let next = track.partition_point(|&(key_time, _, _)| key_time <= time);
+    if next == 0 {
+        return (track[0].1, track[0].2);
+    }
+    if next == track.len() {
+        let last = track[track.len() - 1];
+        return (last.1, last.2);
+    }
+
+    let (start_time, start_position, start_rotation) = track[next - 1];
+    let (end_time, end_position, end_rotation) = track[next];
+    let alpha = ((time - start_time) / (end_time - start_time)) as f32;
+    (
+        start_position.lerp(end_position, alpha),
+        start_rotation.slerp(end_rotation, alpha),
+    )
+}
+
+/// Remove bones from a hierarchy, attaching kept bones to their nearest kept ancestor
+///
+/// The rest transforms of the removed bones in between are composed into each kept bone's.
+pub(crate) fn collapse_bone_infos(
+    bone_infos: &HashMap<String, NodeInfo>,
+    keep: &dyn Fn(&str) -> bool,
+) -> HashMap<String, NodeInfo> {
+    bone_infos
+        .iter()
+        .filter(|(name, _)| keep(name))
+        .map(|(name, info)| {
+            let (parent, removed) = kept_parent(name, bone_infos, keep);
+            let rest = removed
+                .iter()
+                .map(|ancestor| matrix4x4_to_mat4(&bone_infos[*ancestor].rest_transform))
+                .fold(Mat4::IDENTITY, |composed, rest| composed * rest)
+                * matrix4x4_to_mat4(&info.rest_transform);
+            (
+                name.clone(),
+                NodeInfo {
+                    rest_transform: mat4_to_matrix4x4(&rest),
+                    parent,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Remove the poses of bones, baking their motion into kept descendants
+///
+/// Kept bones under removed ones are re-expressed relative to the hierarchy built by
+/// [`collapse_bone_infos`]. Returns the names of the removed bones that had poses, sorted.
+pub(crate) fn collapse_bone_keyframes(
+    keyframes: &mut Vec<Keyframe>,
+    bone_infos: &HashMap<String, NodeInfo>,
+    keep: &dyn Fn(&str) -> bool,
+) -> Vec<String> {
+    let posed: BTreeSet<String> = keyframes
+        .iter()
+        .flat_map(|kf| kf.poses.iter().map(|pose| pose.name.clone()))
+        .collect();
+    let removed: BTreeSet<String> = posed.iter().filter(|name| !keep(name)).cloned().collect();
+
+    // Kept bones under removed ancestors are re-expressed in their new parent's space
+    let mut kept: Vec<&String> = bone_infos.keys().filter(|name| keep(name)).collect();
+    kept.sort();
+    let composed: Vec<(&str, Vec<&str>)> = kept
+        .into_iter()
+        .filter_map(|name| {
+            let (_, ancestors) = kept_parent(name, bone_infos, keep);
+            let animated =
+                posed.contains(name) || ancestors.iter().any(|ancestor| posed.contains(*ancestor));
+            (animated && !ancestors.is_empty()).then_some((name.as_str(), ancestors))
+        })
+        .collect();
+
+    if !composed.is_empty() {
+        let tracks = collect_pose_tracks(keyframes);
+        let rests: HashMap<&str, (Vec3, Quat)> = bone_infos
+            .iter()
+            .map(|(name, info)| (name.as_str(), decompose_matrix4x4(&info.rest_transform)))
+            .collect();
+
+        let mut baked: Vec<(f64, Pose)> = Vec::new();
+        for (name, ancestors) in &composed {
+            debug!("Baking {:?} into {}", ancestors, name);
+            let chain: Vec<&str> = ancestors
+                .iter()
+                .copied()
+                .chain(std::iter::once(*name))
+                .collect();
+
+            let times: BTreeSet<u64> = chain
+                .iter()
+                .filter_map(|bone| tracks.get(bone))
+                .flatten()
+                .map(|&(time, _, _)| time.to_bits())
+                .collect();
+
+            for time in times.into_iter().map(f64::from_bits) {
+                let mut rest = (Vec3::ZERO, Quat::IDENTITY);
+                let mut animated = (Vec3::ZERO, Quat::IDENTITY);
+                for bone in &chain {
+                    let (rest_position, rest_rotation) = rests
+                        .get(bone)
+                        .copied()
+                        .unwrap_or((Vec3::ZERO, Quat::IDENTITY));
+                    let (delta_position, delta_rotation) = tracks
+                        .get(bone)
+                        .map(|track| sample_pose_track(track, time))
+                        .unwrap_or((Vec3::ZERO, Quat::IDENTITY));
+
+                    rest = (rest.0 + rest.1 * rest_position, rest.1 * rest_rotation);
+                    animated = (
+                        animated.0 + animated.1 * (rest_position + delta_position),
+                        animated.1 * rest_rotation * delta_rotation,
+                    );
+                }
+
+                baked.push((
+                    time,
+                    Pose::new(
+                        name.to_string(),
+                        cframe_from_glam(
+                            animated.0 - rest.0,
+                            (rest.1.inverse() * animated.1).normalize(),
+                        ),
+                    ),
+                ));
+            }
+        }
+
+        let composed_names: BTreeSet<&str> = composed.iter().map(|(name, _)| *name).collect();
+        for keyframe in keyframes.iter_mut() {
+            keyframe
+                .poses
+                .retain(|pose| !composed_names.contains(pose.name.as_str()));
+        }
+        for (time, pose) in baked {
+            // Baked times all come from existing keyframes, as every track was built from them
+            if let Some(keyframe) = keyframes.iter_mut().find(|kf| kf.time == time) {
+                keyframe.poses.push(pose);
+            }
+        }
+    }
+
+    for keyframe in keyframes.iter_mut() {
+        keyframe.poses.retain(|pose| !removed.contains(&pose.name));
+    }
+    keyframes.retain(|kf| !kf.poses.is_empty());
+
+    removed.into_iter().collect()
+}
+
+/// The nearest kept ancestor of a bone, and the removed ancestors in between, top-down
+fn kept_parent<'a>(
+    name: &str,
+    bone_infos: &'a HashMap<String, NodeInfo>,
+    keep: &dyn Fn(&str) -> bool,
+) -> (Option<String>, Vec<&'a str>) {
+    let mut removed = Vec::new();
+    let mut ancestor = bone_infos.get(name).and_then(|info| info.parent.as_deref());
+    while let Some(name) = ancestor {
+        let Some((name, info)) = bone_infos.get_key_value(name) else {
+            break;
+        };
+        if keep(name) {
+            removed.reverse();
+            return (Some(name.clone()), removed);
+        }
+        removed.push(name.as_str());
+        ancestor = info.parent.as_deref();
+    }
+    removed.reverse();
+    (None, removed)
+}
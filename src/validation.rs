@@ -0,0 +1,188 @@
+//! Size and budget checks for generated KeyframeSequences
+
+use std::fmt;
+
+use anyhow::Result;
+use rbx_dom_weak::WeakDom;
+use rbx_types::Ref;
+
+use crate::types::Keyframe;
+
+/// Limits a KeyframeSequence is checked against, each `None` when unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    /// Maximum number of Keyframe instances
+    pub max_keyframes: Option<usize>,
+    /// Maximum number of Pose instances across all keyframes
+    pub max_poses: Option<usize>,
+    /// Maximum duration in seconds
+    pub max_duration: Option<f64>,
+    /// Maximum serialized `.rbxm` size in bytes
+    pub max_size: Option<usize>,
+    /// Maximum number of poses in a single keyframe
+    pub max_bones_per_keyframe: Option<usize>,
+}
+
+/// Measurements of a KeyframeSequence
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SequenceStats {
+    /// Number of keyframes
+    pub keyframe_count: usize,
+    /// Number of poses across all keyframes
+    pub pose_count: usize,
+    /// Time of the last keyframe in seconds
+    pub duration: f64,
+    /// Size of the sequence serialized as `.rbxm`, in bytes
+    pub serialized_size: usize,
+    /// Largest number of poses in a single keyframe
+    pub max_bones_per_keyframe: usize,
+    /// Average number of poses per keyframe
+    pub average_bones_per_keyframe: f64,
+}
+
+/// A budget limit that can be exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetLimit {
+    /// [`Budget::max_keyframes`]
+    Keyframes,
+    /// [`Budget::max_poses`]
+    Poses,
+    /// [`Budget::max_duration`]
+    Duration,
+    /// [`Budget::max_size`]
+    Size,
+    /// [`Budget::max_bones_per_keyframe`]
+    BonesPerKeyframe,
+}
+
+impl fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Keyframes => write!(f, "keyframe count"),
+            Self::Poses => write!(f, "pose count"),
+            Self::Duration => write!(f, "duration"),
+            Self::Size => write!(f, "serialized size"),
+            Self::BonesPerKeyframe => write!(f, "bones per keyframe"),
+        }
+    }
+}
+
+/// A measurement that exceeded its budget
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetViolation {
+    /// The exceeded limit
+    pub limit: BudgetLimit,
+    /// The measured value
+    pub actual: f64,
+    /// The configured maximum
+    pub max: f64,
+}
+
+impl fmt::Display for BudgetViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} exceeds the budget of {}",
+            self.limit, self.actual, self.max
+        )
+    }
+}
+
+/// Measurements of a sequence and the budgets it exceeded
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ValidationReport {
+    /// Measurements of the sequence
+    pub stats: SequenceStats,
+    /// Every exceeded budget limit
+    pub violations: Vec<BudgetViolation>,
+}
+
+impl ValidationReport {
+    /// Whether the sequence stayed within every budget limit
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Measure a KeyframeSequence built from `keyframes` and check it against a budget
+pub fn validate_sequence(
+    keyframes: &[Keyframe],
+    kfs: &WeakDom,
+    budget: &Budget,
+) -> Result<ValidationReport> {
+    let stats = measure_sequence(keyframes, kfs)?;
+    let mut violations = Vec::new();
+
+    let mut check = |limit: BudgetLimit, actual: f64, max: Option<f64>| {
+        if let Some(max) = max
+            && actual > max
+        {
+            violations.push(BudgetViolation { limit, actual, max });
+        }
+    };
+
+    check(
+        BudgetLimit::Keyframes,
+        stats.keyframe_count as f64,
+        budget.max_keyframes.map(|max| max as f64),
+    );
+    check(
+        BudgetLimit::Poses,
+        stats.pose_count as f64,
+        budget.max_poses.map(|max| max as f64),
+    );
+    check(BudgetLimit::Duration, stats.duration, budget.max_duration);
+    check(
+        BudgetLimit::Size,
+        stats.serialized_size as f64,
+        budget.max_size.map(|max| max as f64),
+    );
+    check(
+        BudgetLimit::BonesPerKeyframe,
+        stats.max_bones_per_keyframe as f64,
+        budget.max_bones_per_keyframe.map(|max| max as f64),
+    );
+
+    Ok(ValidationReport { stats, violations })
+}
+
+/// Measure a KeyframeSequence built from `keyframes`
+pub fn measure_sequence(keyframes: &[Keyframe], kfs: &WeakDom) -> Result<SequenceStats> {
+    let mut serialized = Vec::new();
+    rbx_binary::to_writer(&mut serialized, kfs, &[kfs.root_ref()])?;
+
+    // Counted in the sequence rather than `keyframes`, which lacks placeholder poses
+    let poses_per_keyframe: Vec<usize> = kfs
+        .root()
+        .children()
+        .iter()
+        .filter_map(|&keyframe| kfs.get_by_ref(keyframe))
+        .filter(|keyframe| keyframe.class.as_str() == "Keyframe")
+        .map(|keyframe| count_poses(kfs, keyframe.children()))
+        .collect();
+    let pose_count: usize = poses_per_keyframe.iter().sum();
+    let average_bones_per_keyframe = if keyframes.is_empty() {
+        0.0
+    } else {
+        pose_count as f64 / keyframes.len() as f64
+    };
+
+    Ok(SequenceStats {
+        keyframe_count: keyframes.len(),
+        pose_count,
+        duration: keyframes.iter().map(|kf| kf.time).fold(0.0, f64::max),
+        serialized_size: serialized.len(),
+        max_bones_per_keyframe: poses_per_keyframe.iter().copied().max().unwrap_or(0),
+        average_bones_per_keyframe,
+    })
+}
+
+/// The number of Pose instances among `children` and their descendants
+fn count_poses(kfs: &WeakDom, children: &[Ref]) -> usize {
+    children
+        .iter()
+        .filter_map(|&child| kfs.get_by_ref(child))
+        .filter(|child| child.class.as_str() == "Pose")
+        .map(|pose| 1 + count_poses(kfs, pose.children()))
+        .sum()
+}
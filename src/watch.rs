@@ -0,0 +1,94 @@
+//! Detecting finished writes to watched input files
+//!
+//! Watching polls each file's size and modification time. Exporters write large files in
+//! several steps, so a change only counts once the file has stopped changing for the
+//! debounce delay. Files that appear count as changed; files that disappear are forgotten.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// What polling knows about a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileState {
+    /// Last modification time, if the platform reports one
+    pub modified: Option<SystemTime>,
+    /// Size in bytes
+    pub len: u64,
+}
+
+impl FileState {
+    /// Read a file's state, or `None` if it can't be read
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// The states of every file that can be read
+pub fn snapshot<'a>(paths: impl IntoIterator<Item = &'a Path>) -> HashMap<PathBuf, FileState> {
+    paths
+        .into_iter()
+        .filter_map(|path| Some((path.to_path_buf(), FileState::of(path)?)))
+        .collect()
+}
+
+/// Turns successive snapshots into the files whose changes have settled
+#[derive(Debug, Clone)]
+pub struct Debouncer {
+    delay: Duration,
+    settled: HashMap<PathBuf, FileState>,
+    pending: HashMap<PathBuf, (FileState, Instant)>,
+}
+
+impl Debouncer {
+    /// Create a debouncer that waits `delay` after a file's last change
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            settled: HashMap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Record files as up to date without reporting them
+    pub fn prime(&mut self, states: HashMap<PathBuf, FileState>) {
+        self.settled.extend(states);
+    }
+
+    /// Take a new snapshot taken at `now`; returns the files that finished changing, sorted
+    pub fn update(&mut self, now: Instant, states: HashMap<PathBuf, FileState>) -> Vec<PathBuf> {
+        self.settled.retain(|path, _| states.contains_key(path));
+        self.pending.retain(|path, _| states.contains_key(path));
+
+        let mut ready = Vec::new();
+        for (path, state) in states {
+            if self.settled.get(&path) == Some(&state) {
+                self.pending.remove(&path);
+                continue;
+            }
+            match self.pending.get(&path) {
+                Some(&(pending, since)) if pending == state => {
+                    if now.duration_since(since) >= self.delay {
+                        self.pending.remove(&path);
+                        self.settled.insert(path.clone(), state);
+                        ready.push(path);
+                    }
+                }
+                _ => {
+                    self.pending.insert(path, (state, now));
+                }
+            }
+        }
+        ready.sort();
+        ready
+    }
+
+    /// Whether any file is still waiting to settle
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
@@ -5,6 +5,51 @@ use std::collections::{HashMap, HashSet};
 use anim2rbx::AnimationConverter;
 use rbx_types::{CFrame, Matrix3, Vector3};
 
+/// Builders shared by the test modules below
+mod helpers {
+    use std::collections::HashMap;
+
+    use anim2rbx::utils::{cframe_from_glam, mat4_to_matrix4x4};
+    use anim2rbx::{Keyframe, NodeInfo, Pose};
+    use glam::{Mat4, Quat, Vec3};
+
+    /// A bone whose rest transform is `rotation` then `translation` in its parent's space
+    pub fn node(parent: Option<&str>, rotation: Quat, translation: Vec3) -> NodeInfo {
+        NodeInfo {
+            rest_transform: mat4_to_matrix4x4(&Mat4::from_rotation_translation(
+                rotation,
+                translation,
+            )),
+            parent: parent.map(str::to_string),
+        }
+    }
+
+    /// A bone hierarchy from `(name, parent, rotation, translation)` rows
+    pub fn hierarchy(bones: &[(&str, Option<&str>, Quat, Vec3)]) -> HashMap<String, NodeInfo> {
+        bones
+            .iter()
+            .map(|&(name, parent, rotation, translation)| {
+                (name.to_string(), node(parent, rotation, translation))
+            })
+            .collect()
+    }
+
+    pub fn pose(name: &str, position: Vec3, rotation: Quat) -> Pose {
+        Pose::new(name.to_string(), cframe_from_glam(position, rotation))
+    }
+
+    /// A keyframe from `(bone, position, rotation)` rows
+    pub fn keyframe(time: f64, poses: &[(&str, Vec3, Quat)]) -> Keyframe {
+        Keyframe {
+            time,
+            poses: poses
+                .iter()
+                .map(|&(name, position, rotation)| pose(name, position, rotation))
+                .collect(),
+        }
+    }
+}
+
 #[test]
 fn test_convert_animation_file() {
     let converter = AnimationConverter::new(true, 1e-5);
@@ -49,9 +94,9 @@ fn test_keyframe_creation() {
     use anim2rbx::{Keyframe, Pose};
     use rbx_types::{CFrame, Matrix3, Vector3};
 
-    let pose = Pose {
-        name: "TestBone".to_string(),
-        cframe: CFrame::new(
+    let pose = Pose::new(
+        "TestBone".to_string(),
+        CFrame::new(
             Vector3::new(0.0, 0.0, 0.0),
             Matrix3 {
                 x: Vector3::new(1.0, 0.0, 0.0),
@@ -59,7 +104,7 @@ fn test_keyframe_creation() {
                 z: Vector3::new(0.0, 0.0, 1.0),
             },
         ),
-    };
+    );
 
     let keyframe = Keyframe {
         time: 0.5,
@@ -84,10 +129,7 @@ fn test_cframe_components() {
     };
 
     let cframe = CFrame::new(position, orientation);
-    let pose = Pose {
-        name: "TestBone".to_string(),
-        cframe,
-    };
+    let pose = Pose::new("TestBone".to_string(), cframe);
 
     assert_eq!(pose.cframe.position.x, 1.0);
     assert_eq!(pose.cframe.position.y, 2.0);
@@ -111,24 +153,24 @@ fn test_keyframe_sorting_and_validation() {
     let mut keyframes = vec![
         Keyframe {
             time: 1.0,
-            poses: vec![Pose {
-                name: "Bone1".to_string(),
-                cframe: CFrame::new(Vector3::new(1.0, 0.0, 0.0), identity_matrix),
-            }],
+            poses: vec![Pose::new(
+                "Bone1".to_string(),
+                CFrame::new(Vector3::new(1.0, 0.0, 0.0), identity_matrix),
+            )],
         },
         Keyframe {
             time: 0.5,
-            poses: vec![Pose {
-                name: "Bone1".to_string(),
-                cframe: CFrame::new(Vector3::new(0.5, 0.0, 0.0), identity_matrix),
-            }],
+            poses: vec![Pose::new(
+                "Bone1".to_string(),
+                CFrame::new(Vector3::new(0.5, 0.0, 0.0), identity_matrix),
+            )],
         },
         Keyframe {
             time: 0.0,
-            poses: vec![Pose {
-                name: "Bone1".to_string(),
-                cframe: CFrame::new(Vector3::new(0.0, 0.0, 0.0), identity_matrix),
-            }],
+            poses: vec![Pose::new(
+                "Bone1".to_string(),
+                CFrame::new(Vector3::new(0.0, 0.0, 0.0), identity_matrix),
+            )],
         },
     ];
 
@@ -206,18 +248,18 @@ fn test_multiple_poses_per_keyframe() {
     };
 
     let poses = vec![
-        Pose {
-            name: "LeftArm".to_string(),
-            cframe: CFrame::new(Vector3::new(-1.0, 0.0, 0.0), identity_matrix),
-        },
-        Pose {
-            name: "RightArm".to_string(),
-            cframe: CFrame::new(Vector3::new(1.0, 0.0, 0.0), identity_matrix),
-        },
-        Pose {
-            name: "Head".to_string(),
-            cframe: CFrame::new(Vector3::new(0.0, 1.0, 0.0), identity_matrix),
-        },
+        Pose::new(
+            "LeftArm".to_string(),
+            CFrame::new(Vector3::new(-1.0, 0.0, 0.0), identity_matrix),
+        ),
+        Pose::new(
+            "RightArm".to_string(),
+            CFrame::new(Vector3::new(1.0, 0.0, 0.0), identity_matrix),
+        ),
+        Pose::new(
+            "Head".to_string(),
+            CFrame::new(Vector3::new(0.0, 1.0, 0.0), identity_matrix),
+        ),
     ];
 
     let keyframe = Keyframe { time: 0.0, poses };
@@ -315,6 +357,7 @@ fn test_empty_keyframe_sequence() {
 }
 
 #[test]
+#[allow(clippy::excessive_precision)]
 fn test_floating_point_precision() {
     use anim2rbx::{Keyframe, Pose};
     use rbx_types::{CFrame, Matrix3, Vector3};
@@ -328,10 +371,10 @@ fn test_floating_point_precision() {
         z: Vector3::new(0.0, 0.0, 1.0),
     };
 
-    let pose = Pose {
-        name: "PrecisionBone".to_string(),
-        cframe: CFrame::new(precise_position, identity_matrix),
-    };
+    let pose = Pose::new(
+        "PrecisionBone".to_string(),
+        CFrame::new(precise_position, identity_matrix),
+    );
 
     let keyframe = Keyframe {
         time: 0.123456789,
@@ -344,6 +387,17 @@ fn test_floating_point_precision() {
     assert!((keyframe.poses[0].cframe.position.y + 9.87654321098765).abs() < f32::EPSILON);
 }
 
+#[test]
+fn test_angle_tolerance_builder() {
+    let converter = AnimationConverter::default().with_angle_tolerance(0.25);
+
+    assert_eq!(converter.angle_tolerance, 0.25);
+    assert_eq!(
+        converter.comparison_tolerance(),
+        anim2rbx::Tolerance::new(converter.epsilon, 0.25)
+    );
+}
+
 #[test]
 fn test_api_consistency() {
     // Ensure all three API patterns produce equivalent results
@@ -460,6 +514,52 @@ mod utils_tests {
         assert!(anim2rbx::utils::approx_equal_vec3(&v5, &v6, 0.15));
     }
 
+    #[test]
+    fn test_cframe_within_tolerance_translation() {
+        let tolerance = anim2rbx::Tolerance::new(0.1, 1.0);
+        let identity = Matrix3 {
+            x: Vector3::new(1.0, 0.0, 0.0),
+            y: Vector3::new(0.0, 1.0, 0.0),
+            z: Vector3::new(0.0, 0.0, 1.0),
+        };
+        let a = CFrame::new(Vector3::new(0.0, 0.0, 0.0), identity);
+        let near = CFrame::new(Vector3::new(0.05, 0.05, 0.05), identity);
+        let far = CFrame::new(Vector3::new(0.08, 0.08, 0.0), identity);
+
+        // Distance is Euclidean, not per component
+        assert!(anim2rbx::utils::cframe_within_tolerance(
+            &a, &near, tolerance
+        ));
+        assert!(!anim2rbx::utils::cframe_within_tolerance(
+            &a, &far, tolerance
+        ));
+    }
+
+    #[test]
+    fn test_cframe_within_tolerance_rotation() {
+        use anim2rbx::utils::{cframe_from_glam, cframe_within_tolerance, rotation_angle_between};
+        use glam::{Quat, Vec3};
+
+        let a = cframe_from_glam(Vec3::ZERO, Quat::IDENTITY);
+        let b = cframe_from_glam(Vec3::ZERO, Quat::from_rotation_z(2.0_f32.to_radians()));
+
+        assert!((rotation_angle_between(&a, &b) - 2.0).abs() < 1e-3);
+        assert!(cframe_within_tolerance(
+            &a,
+            &b,
+            anim2rbx::Tolerance::new(0.0, 2.5)
+        ));
+        assert!(!cframe_within_tolerance(
+            &a,
+            &b,
+            anim2rbx::Tolerance::new(0.0, 1.5)
+        ));
+
+        // Tiny rotations are still measured precisely
+        let tiny = cframe_from_glam(Vec3::ZERO, Quat::from_rotation_x(1e-4_f32.to_radians()));
+        assert!((rotation_angle_between(&a, &tiny) - 1e-4).abs() < 1e-6);
+    }
+
     #[test]
     fn test_zero_epsilon_strict_equality() {
         let v1 = Vector3::new(1.0, 2.0, 3.0);
@@ -470,3 +570,2678 @@ mod utils_tests {
         assert!(!anim2rbx::utils::approx_equal_vec3(&v1, &v3, 0.0));
     }
 }
+
+mod optimize_tests {
+    use anim2rbx::optimize::{Quantization, quantize_keyframes, reduce_keyframes};
+    use anim2rbx::utils::cframe_from_glam;
+    use anim2rbx::{Keyframe, Pose, Tolerance};
+    use glam::{Quat, Vec3};
+
+    fn track(name: &str, samples: &[(f64, Vec3, Quat)]) -> Vec<Keyframe> {
+        samples
+            .iter()
+            .map(|&(time, position, rotation)| Keyframe {
+                time,
+                poses: vec![Pose::new(
+                    name.to_string(),
+                    cframe_from_glam(position, rotation),
+                )],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reduce_linear_motion_to_endpoints() {
+        let samples: Vec<_> = (0..=10)
+            .map(|i| {
+                let t = i as f64 / 10.0;
+                (
+                    t,
+                    Vec3::new(t as f32 * 2.0, 0.0, 0.0),
+                    Quat::from_rotation_y(t as f32),
+                )
+            })
+            .collect();
+        let mut keyframes = track("Bone", &samples);
+
+        let report = reduce_keyframes(&mut keyframes, Tolerance::new(0.001, 0.1));
+
+        assert_eq!(keyframes.len(), 2);
+        assert_eq!(keyframes[0].time, 0.0);
+        assert_eq!(keyframes[1].time, 1.0);
+        assert_eq!(report.poses_before(), 11);
+        assert_eq!(report.poses_after(), 2);
+        assert_eq!(report.keyframes_before, 11);
+        assert_eq!(report.keyframes_after, 2);
+    }
+
+    #[test]
+    fn test_reduce_keeps_poses_outside_tolerance() {
+        let mut keyframes = track(
+            "Bone",
+            &[
+                (0.0, Vec3::ZERO, Quat::IDENTITY),
+                (0.5, Vec3::new(0.0, 1.0, 0.0), Quat::IDENTITY),
+                (1.0, Vec3::ZERO, Quat::IDENTITY),
+            ],
+        );
+
+        let report = reduce_keyframes(&mut keyframes, Tolerance::new(0.01, 0.5));
+
+        assert_eq!(keyframes.len(), 3);
+        assert_eq!(report.poses_after(), 3);
+        assert_eq!(report.removed_ratio(), 0.0);
+    }
+
+    #[test]
+    fn test_reduce_respects_angular_tolerance() {
+        let samples = [
+            (0.0, Vec3::ZERO, Quat::IDENTITY),
+            (0.5, Vec3::ZERO, Quat::from_rotation_x(1.0_f32.to_radians())),
+            (1.0, Vec3::ZERO, Quat::IDENTITY),
+        ];
+
+        let mut loose = track("Bone", &samples);
+        reduce_keyframes(&mut loose, Tolerance::new(0.01, 2.0));
+        assert_eq!(loose.len(), 2);
+
+        let mut strict = track("Bone", &samples);
+        reduce_keyframes(&mut strict, Tolerance::new(0.01, 0.5));
+        assert_eq!(strict.len(), 3);
+    }
+
+    #[test]
+    fn test_quantize_rounds_positions_and_keeps_orthonormal() {
+        let mut keyframes = track(
+            "Bone",
+            &[(
+                0.0,
+                Vec3::new(1.23456, 1e-8, -0.0004),
+                Quat::from_rotation_y(10.004_f32.to_radians()),
+            )],
+        );
+
+        quantize_keyframes(&mut keyframes, Quantization::new(0.001, 0.01));
+
+        let cframe = &keyframes[0].poses[0].cframe;
+        assert!((cframe.position.x - 1.235).abs() < 1e-6);
+        assert_eq!(cframe.position.y, 0.0);
+        assert!(cframe.position.z.abs() < 1e-6);
+
+        let (_, rotation) = anim2rbx::utils::cframe_to_glam(cframe);
+        let angle = rotation.angle_between(Quat::IDENTITY).to_degrees();
+        assert!((angle - 10.0).abs() < 1e-3);
+
+        let m = &cframe.orientation;
+        let rows = [
+            Vec3::new(m.x.x, m.x.y, m.x.z),
+            Vec3::new(m.y.x, m.y.y, m.y.z),
+            Vec3::new(m.z.x, m.z.y, m.z.z),
+        ];
+        for (i, a) in rows.iter().enumerate() {
+            assert!((a.length() - 1.0).abs() < 1e-5);
+            for b in &rows[i + 1..] {
+                assert!(a.dot(*b).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_quantize_lets_identical_filter_catch_noise() {
+        let mut keyframes = track(
+            "Noisy",
+            &[
+                (0.0, Vec3::new(1e-7, 0.0, 0.0), Quat::IDENTITY),
+                (
+                    0.5,
+                    Vec3::new(-2e-7, 3e-8, 0.0),
+                    Quat::from_rotation_x(1e-6),
+                ),
+            ],
+        );
+
+        quantize_keyframes(&mut keyframes, Quantization::default());
+        let removed =
+            anim2rbx::filter_identical_bone_poses(&mut keyframes, Tolerance::new(0.0, 0.0));
+
+        assert_eq!(removed.len(), 1);
+        assert!(keyframes.is_empty());
+    }
+
+    #[test]
+    fn test_reduce_reports_per_bone() {
+        let mut keyframes = track(
+            "Moving",
+            &[
+                (0.0, Vec3::ZERO, Quat::IDENTITY),
+                (0.5, Vec3::new(0.0, 1.0, 0.0), Quat::IDENTITY),
+                (1.0, Vec3::ZERO, Quat::IDENTITY),
+            ],
+        );
+        for keyframe in &mut keyframes {
+            keyframe.poses.push(Pose::new(
+                "Still".to_string(),
+                cframe_from_glam(Vec3::ZERO, Quat::IDENTITY),
+            ));
+        }
+
+        let report = reduce_keyframes(&mut keyframes, Tolerance::default());
+
+        assert_eq!(report.bones.len(), 2);
+        let moving = report.bones.iter().find(|b| b.name == "Moving").unwrap();
+        let still = report.bones.iter().find(|b| b.name == "Still").unwrap();
+        assert_eq!((moving.poses_before, moving.poses_after), (3, 3));
+        assert_eq!((still.poses_before, still.poses_after), (3, 2));
+        assert_eq!(keyframes[1].poses.len(), 1);
+    }
+}
+
+mod filter_tests {
+    use anim2rbx::utils::cframe_from_glam;
+    use anim2rbx::{
+        BoneRemovalReason, Keyframe, Pose, Tolerance, filter_identical_bone_poses,
+        filter_rest_pose_bones,
+    };
+    use glam::{Quat, Vec3};
+
+    fn keyframes(bones: &[(&str, &[Vec3])]) -> Vec<Keyframe> {
+        let frames = bones.iter().map(|(_, p)| p.len()).max().unwrap_or(0);
+        (0..frames)
+            .map(|i| Keyframe {
+                time: i as f64 / 30.0,
+                poses: bones
+                    .iter()
+                    .filter_map(|(name, positions)| {
+                        positions.get(i).map(|&position| {
+                            Pose::new(name.to_string(), cframe_from_glam(position, Quat::IDENTITY))
+                        })
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rest_pose_filter_removes_wobbling_bones() {
+        let wobble = [
+            Vec3::new(0.001, 0.0, 0.0),
+            Vec3::new(-0.001, 0.0, 0.0),
+            Vec3::ZERO,
+        ];
+        let moving = [Vec3::ZERO, Vec3::new(1.0, 0.0, 0.0), Vec3::ZERO];
+        let mut kfs = keyframes(&[("Wobble", &wobble), ("Moving", &moving)]);
+
+        let removed = filter_rest_pose_bones(&mut kfs, Tolerance::new(0.01, 0.1));
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].name, "Wobble");
+        assert_eq!(removed[0].reason, BoneRemovalReason::RestPose);
+        assert!(
+            kfs.iter()
+                .all(|kf| kf.poses.iter().all(|p| p.name == "Moving"))
+        );
+    }
+
+    #[test]
+    fn test_rest_pose_filter_removes_single_rest_key() {
+        let mut kfs = keyframes(&[("RestOnly", &[Vec3::ZERO])]);
+
+        let removed = filter_rest_pose_bones(&mut kfs, Tolerance::new(1e-5, 1e-3));
+
+        assert_eq!(removed.len(), 1);
+        assert!(kfs.is_empty());
+    }
+
+    #[test]
+    fn test_rest_pose_filter_keeps_static_offset_bones() {
+        let offset = [Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 2.0, 0.0)];
+        let mut kfs = keyframes(&[("Offset", &offset)]);
+
+        let removed = filter_rest_pose_bones(&mut kfs, Tolerance::new(0.01, 0.1));
+        assert!(removed.is_empty());
+
+        let removed = filter_identical_bone_poses(&mut kfs, Tolerance::new(0.01, 0.1));
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].reason, BoneRemovalReason::IdenticalPoses);
+    }
+}
+
+mod looping_tests {
+    use anim2rbx::looping::{LoopConfig, LoopSeam, detect_loop, prepare_loop};
+    use anim2rbx::utils::{cframe_from_glam, cframe_to_glam};
+    use anim2rbx::{Keyframe, Pose, Tolerance};
+    use glam::{Quat, Vec3};
+
+    fn clip(end_offset: Vec3) -> Vec<Keyframe> {
+        (0..=4)
+            .map(|i| {
+                let t = i as f32 / 4.0;
+                let position = Vec3::new((t * std::f32::consts::TAU).sin(), 0.0, 0.0)
+                    + end_offset * if i == 4 { 1.0 } else { 0.0 };
+                Keyframe {
+                    time: t as f64,
+                    poses: vec![Pose::new(
+                        "Hips".to_string(),
+                        cframe_from_glam(position, Quat::IDENTITY),
+                    )],
+                }
+            })
+            .collect()
+    }
+
+    fn position(keyframe: &Keyframe) -> Vec3 {
+        cframe_to_glam(&keyframe.poses[0].cframe).0
+    }
+
+    #[test]
+    fn test_detect_loop_matching_ends() {
+        let report = detect_loop(&clip(Vec3::ZERO), Tolerance::new(0.01, 1.0));
+
+        assert!(report.looped);
+        assert!(report.score > 0.99);
+        assert_eq!(report.worst_bone.as_deref(), Some("Hips"));
+    }
+
+    #[test]
+    fn test_detect_loop_mismatched_ends() {
+        let report = detect_loop(&clip(Vec3::new(0.0, 1.0, 0.0)), Tolerance::new(0.01, 1.0));
+
+        assert!(!report.looped);
+        assert!(report.score < 0.5);
+        assert!((report.max_position_error - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_prepare_loop_match_start() {
+        let mut keyframes = clip(Vec3::new(0.0, 0.02, 0.0));
+        let report = prepare_loop(
+            &mut keyframes,
+            LoopConfig {
+                tolerance: Tolerance::new(0.05, 5.0),
+                seam: LoopSeam::MatchStart,
+                force: false,
+            },
+        );
+
+        assert!(report.looped && report.seam_fixed);
+        assert!(position(&keyframes[4]).distance(position(&keyframes[0])) < 1e-5);
+    }
+
+    #[test]
+    fn test_prepare_loop_blend_spreads_offset() {
+        let mut keyframes = clip(Vec3::new(0.0, 0.04, 0.0));
+        let before = position(&keyframes[3]);
+        prepare_loop(
+            &mut keyframes,
+            LoopConfig {
+                tolerance: Tolerance::new(0.05, 5.0),
+                seam: LoopSeam::Blend { frames: 2 },
+                force: false,
+            },
+        );
+
+        assert!(position(&keyframes[4]).distance(position(&keyframes[0])) < 1e-5);
+        // The middle of the blend window moves half of the seam offset
+        assert!((position(&keyframes[3]).y - before.y + 0.02).abs() < 1e-5);
+        assert!(position(&keyframes[2]).y.abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_prepare_loop_skips_non_loops_unless_forced() {
+        let mut keyframes = clip(Vec3::new(0.0, 1.0, 0.0));
+        let config = LoopConfig {
+            seam: LoopSeam::MatchStart,
+            ..Default::default()
+        };
+
+        let report = prepare_loop(&mut keyframes, config);
+        assert!(!report.looped && !report.seam_fixed);
+
+        let report = prepare_loop(
+            &mut keyframes,
+            LoopConfig {
+                force: true,
+                ..config
+            },
+        );
+        assert!(report.looped && report.seam_fixed);
+        assert!(position(&keyframes[4]).distance(position(&keyframes[0])) < 1e-5);
+    }
+}
+
+mod validation_tests {
+    use std::collections::HashMap;
+
+    use anim2rbx::utils::cframe_from_glam;
+    use anim2rbx::validation::{Budget, BudgetLimit, validate_sequence};
+    use anim2rbx::{
+        Keyframe, Pose, create_keyframe_sequence_dom,
+        create_keyframe_sequence_dom_with_placeholders,
+    };
+    use glam::{Quat, Vec3};
+
+    use crate::helpers::{hierarchy, keyframe};
+
+    fn keyframes() -> Vec<Keyframe> {
+        (0..3)
+            .map(|i| Keyframe {
+                time: i as f64 * 0.5,
+                poses: (0..=i)
+                    .map(|b| {
+                        Pose::new(
+                            format!("Bone{}", b),
+                            cframe_from_glam(Vec3::ZERO, Quat::IDENTITY),
+                        )
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_validation_stats() {
+        let keyframes = keyframes();
+        let kfs = create_keyframe_sequence_dom(&keyframes, &HashMap::new());
+
+        let report = validate_sequence(&keyframes, &kfs, &Budget::default()).unwrap();
+
+        assert!(report.passed());
+        assert_eq!(report.stats.keyframe_count, 3);
+        assert_eq!(report.stats.pose_count, 6);
+        assert_eq!(report.stats.duration, 1.0);
+        assert_eq!(report.stats.max_bones_per_keyframe, 3);
+        assert_eq!(report.stats.average_bones_per_keyframe, 2.0);
+        assert!(report.stats.serialized_size > 0);
+    }
+
+    #[test]
+    fn test_validation_budget_violations() {
+        let keyframes = keyframes();
+        let kfs = create_keyframe_sequence_dom(&keyframes, &HashMap::new());
+        let budget = Budget {
+            max_keyframes: Some(2),
+            max_duration: Some(2.0),
+            max_size: Some(1),
+            ..Default::default()
+        };
+
+        let report = validate_sequence(&keyframes, &kfs, &budget).unwrap();
+
+        let limits: Vec<_> = report.violations.iter().map(|v| v.limit).collect();
+        assert_eq!(limits, vec![BudgetLimit::Keyframes, BudgetLimit::Size]);
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn test_validation_counts_placeholder_poses() {
+        let infos = hierarchy(&[
+            ("Root", None, Quat::IDENTITY, Vec3::ZERO),
+            ("Arm", Some("Root"), Quat::IDENTITY, Vec3::X),
+        ]);
+        let keyframes = vec![
+            keyframe(0.0, &[("Arm", Vec3::ZERO, Quat::IDENTITY)]),
+            keyframe(1.0, &[("Arm", Vec3::ZERO, Quat::IDENTITY)]),
+        ];
+        let kfs = create_keyframe_sequence_dom_with_placeholders(&keyframes, &infos);
+
+        let report = validate_sequence(&keyframes, &kfs, &Budget::default()).unwrap();
+
+        // Root only has placeholder poses, which still count towards the budget
+        assert_eq!(report.stats.pose_count, 4);
+        assert_eq!(report.stats.max_bones_per_keyframe, 2);
+        assert_eq!(report.stats.average_bones_per_keyframe, 2.0);
+    }
+}
+
+mod remap_tests {
+    use anim2rbx::remap::BoneMap;
+    use anim2rbx::utils::cframe_from_glam;
+    use anim2rbx::{BoneRemovalReason, Keyframe, Pose, RemovedBone};
+    use glam::{Quat, Vec3};
+
+    use crate::helpers::{hierarchy, keyframe};
+
+    const MAPPING: &str = r#"
+        [bones]
+        "mixamorig:LeftUpLeg" = "LeftUpperLeg"
+        "mixamorig:Spine1" = ""
+
+        [[rules]]
+        pattern = "_end$"
+        replace = ""
+
+        [[rules]]
+        pattern = "^mixamorig:(.*)$"
+        replace = "$1"
+    "#;
+
+    #[test]
+    fn test_map_names() {
+        let map = BoneMap::from_toml_str(MAPPING).unwrap();
+
+        assert_eq!(
+            map.map_name("mixamorig:LeftUpLeg").as_deref(),
+            Some("LeftUpperLeg")
+        );
+        assert_eq!(map.map_name("mixamorig:Hips").as_deref(), Some("Hips"));
+        assert_eq!(map.map_name("mixamorig:Spine1"), None);
+        assert_eq!(map.map_name("mixamorig:HeadTop_end"), None);
+        assert_eq!(map.map_name("Prop").as_deref(), Some("Prop"));
+    }
+
+    #[test]
+    fn test_json_mapping_with_null() {
+        let map = BoneMap::from_json_str(
+            r#"{ "bones": { "A": "B", "C": null }, "rules": [{ "pattern": "^x", "replace": "y" }] }"#,
+        )
+        .unwrap();
+
+        assert_eq!(map.map_name("A").as_deref(), Some("B"));
+        assert_eq!(map.map_name("C"), None);
+        assert_eq!(map.map_name("xz").as_deref(), Some("yz"));
+    }
+
+    #[test]
+    fn test_invalid_mapping_is_an_error() {
+        assert!(BoneMap::from_toml_str("[[rules]]\npattern = \"(\"\n").is_err());
+        assert!(BoneMap::from_toml_str("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_apply_to_keyframes_and_hierarchy() {
+        let map = BoneMap::from_toml_str(MAPPING).unwrap();
+        let pose = |name: &str| {
+            Pose::new(
+                name.to_string(),
+                cframe_from_glam(Vec3::ZERO, Quat::IDENTITY),
+            )
+        };
+        let mut keyframes = vec![Keyframe {
+            time: 0.0,
+            poses: vec![
+                pose("mixamorig:Hips"),
+                pose("mixamorig:Spine1"),
+                pose("mixamorig:Spine2"),
+            ],
+        }];
+
+        let bone_infos = hierarchy(&[
+            ("mixamorig:Hips", None, Quat::IDENTITY, Vec3::ZERO),
+            (
+                "mixamorig:Spine1",
+                Some("mixamorig:Hips"),
+                Quat::IDENTITY,
+                Vec3::ZERO,
+            ),
+            (
+                "mixamorig:Spine2",
+                Some("mixamorig:Spine1"),
+                Quat::IDENTITY,
+                Vec3::ZERO,
+            ),
+        ]);
+        let dropped = map.apply_to_keyframes(&mut keyframes, &bone_infos);
+        assert_eq!(
+            dropped,
+            vec![RemovedBone {
+                name: "mixamorig:Spine1".to_string(),
+                reason: BoneRemovalReason::Unmapped,
+            }]
+        );
+        let names: Vec<_> = keyframes[0].poses.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Hips", "Spine2"]);
+
+        let mapped = map.apply_to_bone_infos(&bone_infos);
+
+        assert_eq!(mapped.len(), 2);
+        assert_eq!(mapped["Spine2"].parent.as_deref(), Some("Hips"));
+        assert!(mapped["Hips"].parent.is_none());
+    }
+
+    #[test]
+    fn test_collisions_resolve_the_same_way_for_keyframes_and_hierarchy() {
+        let map = BoneMap::from_toml_str("[bones]\nB = \"Target\"\nA = \"Target\"\n").unwrap();
+        let target_x = |keyframes: &[Keyframe]| {
+            assert_eq!(keyframes[0].poses.len(), 1);
+            assert_eq!(keyframes[0].poses[0].name, "Target");
+            keyframes[0].poses[0].cframe.position.x
+        };
+        // A's poses sit at x = 1 and B's at x = 2
+        let posed = || {
+            vec![keyframe(
+                0.0,
+                &[
+                    ("B", Vec3::X * 2.0, Quat::IDENTITY),
+                    ("A", Vec3::X, Quat::IDENTITY),
+                ],
+            )]
+        };
+
+        // Channel order puts B first, but A sorts first and wins in both paths
+        let bone_infos = hierarchy(&[
+            ("A", None, Quat::IDENTITY, Vec3::ZERO),
+            ("B", None, Quat::IDENTITY, Vec3::ZERO),
+        ]);
+        let mut keyframes = posed();
+        let removed = map.apply_to_keyframes(&mut keyframes, &bone_infos);
+        let mapped = map.apply_to_bone_infos(&bone_infos);
+        assert_eq!(target_x(&keyframes), 1.0);
+        assert_eq!(mapped.len(), 1);
+        assert_eq!(
+            removed,
+            vec![RemovedBone {
+                name: "B".to_string(),
+                reason: BoneRemovalReason::MappingCollision,
+            }]
+        );
+
+        // Only B is posed, but A is in the hierarchy, so B's poses are still skipped
+        let mut keyframes = vec![keyframe(0.0, &[("B", Vec3::X * 2.0, Quat::IDENTITY)])];
+        map.apply_to_keyframes(&mut keyframes, &bone_infos);
+        assert!(keyframes.is_empty());
+
+        // A bone in the hierarchy wins over one that is only posed, whatever the order
+        let bone_infos = hierarchy(&[("B", None, Quat::IDENTITY, Vec3::ZERO)]);
+        let mut keyframes = posed();
+        map.apply_to_keyframes(&mut keyframes, &bone_infos);
+        assert_eq!(target_x(&keyframes), 2.0);
+    }
+}
+
+mod retarget_tests {
+    use std::collections::HashMap;
+
+    use anim2rbx::retarget::{Preset, retarget_keyframes, strip_namespace};
+    use anim2rbx::utils::{cframe_to_glam, quat_angle_between};
+    use anim2rbx::{
+        Keyframe, create_keyframe_sequence_dom, create_keyframe_sequence_dom_with_placeholders,
+    };
+    use glam::{Quat, Vec3};
+
+    use crate::helpers::{node, pose};
+
+    fn find(keyframe: &Keyframe, name: &str) -> (Vec3, Quat) {
+        let pose = keyframe.poses.iter().find(|p| p.name == name).unwrap();
+        cframe_to_glam(&pose.cframe)
+    }
+
+    #[test]
+    fn test_preset_hierarchy() {
+        let spec = Preset::MixamoR15.spec();
+        let infos = spec.target_bone_infos();
+
+        assert_eq!(infos.len(), 16);
+        assert!(infos["HumanoidRootPart"].parent.is_none());
+        assert_eq!(infos["UpperTorso"].parent.as_deref(), Some("LowerTorso"));
+        assert_eq!(infos["RightHand"].parent.as_deref(), Some("RightLowerArm"));
+        assert_eq!(strip_namespace("mixamorig1:Spine2"), "Spine2");
+        assert_eq!(strip_namespace("Hips"), "Hips");
+    }
+
+    #[test]
+    fn test_spine_chain_folds_into_upper_torso() {
+        let bone_infos = HashMap::from([
+            (
+                "mixamorig:Hips".to_string(),
+                node(None, Quat::IDENTITY, Vec3::new(0.0, 100.0, 0.0)),
+            ),
+            (
+                "mixamorig:Spine".to_string(),
+                node(Some("mixamorig:Hips"), Quat::IDENTITY, Vec3::Y * 10.0),
+            ),
+            (
+                "mixamorig:Spine1".to_string(),
+                node(Some("mixamorig:Spine"), Quat::IDENTITY, Vec3::Y * 10.0),
+            ),
+            (
+                "mixamorig:Spine2".to_string(),
+                node(Some("mixamorig:Spine1"), Quat::IDENTITY, Vec3::Y * 10.0),
+            ),
+        ]);
+        let step = Quat::from_rotation_y(10f32.to_radians());
+        let keyframes = vec![Keyframe {
+            time: 0.0,
+            poses: vec![
+                pose("mixamorig:Hips", Vec3::new(0.0, 28.0, 0.0), Quat::IDENTITY),
+                pose("mixamorig:Spine", Vec3::ZERO, step),
+                pose("mixamorig:Spine1", Vec3::ZERO, step),
+                pose("mixamorig:Spine2", Vec3::ZERO, step),
+            ],
+        }];
+
+        let retargeted = retarget_keyframes(&keyframes, &bone_infos, &Preset::MixamoR15.spec());
+
+        assert_eq!(retargeted.len(), 1);
+        let (position, rotation) = find(&retargeted[0], "LowerTorso");
+        assert!(position.distance(Vec3::Y) < 1e-4);
+        assert!(quat_angle_between(rotation, Quat::IDENTITY) < 1e-4);
+
+        let (position, rotation) = find(&retargeted[0], "UpperTorso");
+        assert_eq!(position, Vec3::ZERO);
+        let expected = Quat::from_rotation_y(30f32.to_radians());
+        assert!(quat_angle_between(rotation, expected).to_degrees() < 1e-3);
+    }
+
+    #[test]
+    fn test_rest_orientation_is_compensated() {
+        // Mixamo arm bones rest with their own axes rotated away from the model axes
+        let arm_rest = Quat::from_rotation_x(90f32.to_radians());
+        let bone_infos = HashMap::from([
+            ("Hips".to_string(), node(None, Quat::IDENTITY, Vec3::ZERO)),
+            (
+                "LeftArm".to_string(),
+                node(Some("Hips"), arm_rest, Vec3::X * 20.0),
+            ),
+        ]);
+
+        // Lower the T-posed left arm (pointing along +X in Mixamo space) to hang down
+        let lowered = Quat::from_rotation_z(-90f32.to_radians());
+        let local = arm_rest.inverse() * lowered * arm_rest;
+        let keyframes = vec![
+            Keyframe {
+                time: 0.0,
+                poses: vec![pose("LeftArm", Vec3::ZERO, Quat::IDENTITY)],
+            },
+            Keyframe {
+                time: 1.0,
+                poses: vec![pose("LeftArm", Vec3::ZERO, local)],
+            },
+        ];
+
+        let retargeted = retarget_keyframes(&keyframes, &bone_infos, &Preset::MixamoR15.spec());
+
+        // The source rest pose raises the stock R15 arm sideways, away from the body
+        let (_, rotation) = find(&retargeted[0], "LeftUpperArm");
+        let hand_direction = rotation * Vec3::NEG_Y;
+        assert!(hand_direction.distance(Vec3::NEG_X) < 1e-4);
+
+        // A hanging source arm matches the stock R15 rest pose
+        let (_, rotation) = find(&retargeted[1], "LeftUpperArm");
+        assert!(quat_angle_between(rotation, Quat::IDENTITY).to_degrees() < 1e-3);
+    }
+
+    #[test]
+    fn test_r6_hierarchy_and_joint_frames() {
+        let spec = Preset::MixamoR6.spec();
+        let infos = spec.target_bone_infos();
+
+        assert_eq!(infos.len(), 7);
+        assert_eq!(infos["Torso"].parent.as_deref(), Some("HumanoidRootPart"));
+        for limb in ["Head", "Left Arm", "Right Arm", "Left Leg", "Right Leg"] {
+            assert_eq!(infos[limb].parent.as_deref(), Some("Torso"));
+        }
+
+        let bone_infos = HashMap::from([
+            (
+                "mixamorig:Hips".to_string(),
+                node(None, Quat::IDENTITY, Vec3::new(0.0, 100.0, 0.0)),
+            ),
+            (
+                "mixamorig:Spine2".to_string(),
+                node(Some("mixamorig:Hips"), Quat::IDENTITY, Vec3::Y * 30.0),
+            ),
+            (
+                "mixamorig:LeftArm".to_string(),
+                node(Some("mixamorig:Spine2"), Quat::IDENTITY, Vec3::X * 20.0),
+            ),
+            (
+                "mixamorig:LeftForeArm".to_string(),
+                node(Some("mixamorig:LeftArm"), Quat::IDENTITY, Vec3::X * 25.0),
+            ),
+        ]);
+        let keyframes = vec![Keyframe {
+            time: 0.0,
+            poses: vec![
+                pose("mixamorig:Hips", Vec3::new(0.0, 28.0, 0.0), Quat::IDENTITY),
+                pose("mixamorig:LeftArm", Vec3::ZERO, Quat::IDENTITY),
+                pose(
+                    "mixamorig:LeftForeArm",
+                    Vec3::ZERO,
+                    Quat::from_rotation_y(1.0),
+                ),
+            ],
+        }];
+
+        let retargeted = retarget_keyframes(&keyframes, &bone_infos, &spec);
+        let names: Vec<_> = retargeted[0]
+            .poses
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Torso", "Left Arm"]);
+
+        let joint = |name: &str| {
+            spec.parts
+                .iter()
+                .find(|part| part.name == name)
+                .unwrap()
+                .joint_rotation
+        };
+
+        // The Hips translation lands on the Torso, expressed in the RootJoint frame
+        let (position, rotation) = find(&retargeted[0], "Torso");
+        let root_joint = joint("Torso");
+        assert!((root_joint * position).distance(Vec3::Y) < 1e-4);
+        assert!(quat_angle_between(rotation, Quat::IDENTITY) < 1e-4);
+
+        // Through the shoulder frame, the T-posed source raises the arm away from the body
+        let (_, rotation) = find(&retargeted[0], "Left Arm");
+        let shoulder = joint("Left Arm");
+        let part_rotation = shoulder * rotation * shoulder.inverse();
+        assert!((part_rotation * Vec3::NEG_Y).distance(Vec3::NEG_X) < 1e-4);
+    }
+
+    #[test]
+    fn test_placeholder_poses_only_when_requested() {
+        let infos = HashMap::from([
+            ("Parent".to_string(), node(None, Quat::IDENTITY, Vec3::ZERO)),
+            (
+                "Child".to_string(),
+                node(Some("Parent"), Quat::IDENTITY, Vec3::ZERO),
+            ),
+        ]);
+        let keyframes = vec![Keyframe {
+            time: 0.0,
+            poses: vec![pose("Child", Vec3::ZERO, Quat::IDENTITY)],
+        }];
+        let names = |kfs: &rbx_dom_weak::WeakDom| {
+            let mut names: Vec<String> = kfs
+                .descendants()
+                .filter(|instance| instance.class.as_str() == "Pose")
+                .map(|instance| instance.name.clone())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Without retargeting, only posed bones get Pose instances
+        let plain = create_keyframe_sequence_dom(&keyframes, &infos);
+        assert_eq!(names(&plain), vec!["Child"]);
+
+        let filled = create_keyframe_sequence_dom_with_placeholders(&keyframes, &infos);
+        assert_eq!(names(&filled), vec!["Child", "Parent"]);
+        let parent = filled
+            .descendants()
+            .find(|instance| instance.name == "Parent")
+            .unwrap();
+        assert_eq!(parent.children().len(), 1);
+    }
+}
+
+mod root_motion_tests {
+    use std::collections::HashMap;
+
+    use anim2rbx::root_motion::{
+        RootMotionConfig, RootMotionSample, RootMotionTrack, extract_root_motion, find_root_bone,
+    };
+    use anim2rbx::utils::{cframe_from_glam, cframe_to_glam};
+    use anim2rbx::{Keyframe, NodeInfo, Pose};
+    use glam::{Quat, Vec3};
+
+    use crate::helpers::{hierarchy, keyframe};
+
+    fn walk() -> Vec<Keyframe> {
+        (0..3)
+            .map(|i| Keyframe {
+                time: i as f64 * 0.5,
+                poses: vec![
+                    Pose::new(
+                        "Spine".to_string(),
+                        cframe_from_glam(Vec3::ZERO, Quat::IDENTITY),
+                    ),
+                    Pose::new(
+                        "Hips".to_string(),
+                        cframe_from_glam(
+                            Vec3::new(0.0, 1.0 + i as f32 * 0.1, -2.0 * i as f32),
+                            Quat::from_rotation_y(0.25 * i as f32),
+                        ),
+                    ),
+                ],
+            })
+            .collect()
+    }
+
+    fn bone_infos() -> HashMap<String, NodeInfo> {
+        hierarchy(&[
+            ("Root", None, Quat::IDENTITY, Vec3::ZERO),
+            ("Hips", Some("Root"), Quat::IDENTITY, Vec3::ZERO),
+            ("Spine", Some("Hips"), Quat::IDENTITY, Vec3::ZERO),
+        ])
+    }
+
+    #[test]
+    fn test_find_root_bone() {
+        assert_eq!(
+            find_root_bone(&walk(), &bone_infos()).as_deref(),
+            Some("Hips")
+        );
+    }
+
+    #[test]
+    fn test_horizontal_motion_is_stripped() {
+        let mut keyframes = walk();
+        let track =
+            extract_root_motion(&mut keyframes, &bone_infos(), &RootMotionConfig::default())
+                .unwrap();
+
+        assert_eq!(track.bone, "Hips");
+        assert_eq!(track.samples.len(), 3);
+        assert!(
+            track
+                .total_displacement()
+                .distance(Vec3::new(0.0, 0.0, -4.0))
+                < 1e-5
+        );
+
+        let (position, rotation) = cframe_to_glam(&keyframes[2].poses[1].cframe);
+        assert!(position.distance(Vec3::new(0.0, 1.2, 0.0)) < 1e-5);
+        assert!(rotation.angle_between(Quat::from_rotation_y(0.5)) < 1e-3);
+    }
+
+    #[test]
+    fn test_vertical_and_yaw_are_optional() {
+        let mut keyframes = walk();
+        let config = RootMotionConfig {
+            vertical: true,
+            yaw: true,
+            ..Default::default()
+        };
+        let track = extract_root_motion(&mut keyframes, &bone_infos(), &config).unwrap();
+
+        assert!((track.samples[2].yaw - 0.5).abs() < 1e-5);
+        let (position, rotation) = cframe_to_glam(&keyframes[2].poses[1].cframe);
+        assert!(position.distance(Vec3::new(0.0, 1.0, 0.0)) < 1e-5);
+        assert!(rotation.angle_between(Quat::IDENTITY) < 1e-3);
+
+        let luau = track.to_luau();
+        assert!(luau.starts_with("-- Root motion"));
+        assert!(luau.contains("bone = \"Hips\""));
+        assert_eq!(luau.matches("CFrame.new").count(), 3);
+
+        let json: serde_json::Value = serde_json::from_str(&track.to_json()).unwrap();
+        assert_eq!(json["samples"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_yaw_is_measured_about_the_parent_vertical_axis() {
+        // A Z-up style root whose local Y axis lies along the parent's Z axis
+        let rest = Quat::from_rotation_x(std::f32::consts::FRAC_PI_2);
+        let bone_infos = hierarchy(&[("Hips", None, rest, Vec3::ZERO)]);
+        let mut keyframes: Vec<Keyframe> = (0..3)
+            .map(|i| {
+                let turn = Quat::from_rotation_y(0.25 * i as f32);
+                keyframe(
+                    i as f64,
+                    &[("Hips", Vec3::ZERO, rest.inverse() * turn * rest)],
+                )
+            })
+            .collect();
+        let config = RootMotionConfig {
+            yaw: true,
+            ..Default::default()
+        };
+
+        let track = extract_root_motion(&mut keyframes, &bone_infos, &config).unwrap();
+
+        assert!((track.samples[2].yaw - 0.5).abs() < 1e-5);
+        let (_, rotation) = cframe_to_glam(&keyframes[2].poses[0].cframe);
+        assert!(rotation.angle_between(Quat::IDENTITY) < 1e-3);
+    }
+
+    #[test]
+    fn test_slice_interpolates_at_range_start() {
+        let track = RootMotionTrack {
+            bone: "Hips".to_string(),
+            samples: (0..3)
+                .map(|i| RootMotionSample {
+                    time: i as f64,
+                    position: Vec3::new(i as f32 * 2.0, 0.0, 0.0),
+                    yaw: i as f32 * 0.2,
+                })
+                .collect(),
+        };
+
+        let slice = track.slice(0.5, 1.5);
+
+        let times: Vec<f64> = slice.samples.iter().map(|s| s.time).collect();
+        assert_eq!(times, vec![0.0, 0.5, 1.0]);
+        assert!(slice.samples[0].position.length() < 1e-5);
+        assert!(slice.samples[1].position.distance(Vec3::new(1.0, 0.0, 0.0)) < 1e-5);
+        assert!(
+            slice
+                .total_displacement()
+                .distance(Vec3::new(2.0, 0.0, 0.0))
+                < 1e-5
+        );
+        assert!((slice.samples[2].yaw - 0.2).abs() < 1e-5);
+    }
+}
+
+mod selection_tests {
+    use std::collections::HashMap;
+
+    use anim2rbx::selection::BoneSelection;
+    use anim2rbx::utils::{cframe_from_glam, cframe_to_glam, decompose_matrix4x4};
+    use anim2rbx::{Keyframe, NodeInfo, Pose};
+    use glam::{Mat4, Quat, Vec3};
+
+    use crate::helpers::node;
+
+    fn animated(info: &NodeInfo, pose: &Pose) -> Mat4 {
+        let (rest_position, rest_rotation) = decompose_matrix4x4(&info.rest_transform);
+        let (position, rotation) = cframe_to_glam(&pose.cframe);
+        Mat4::from_rotation_translation(rest_rotation * rotation, rest_position + position)
+    }
+
+    fn pose_of<'a>(keyframe: &'a Keyframe, name: &str) -> &'a Pose {
+        keyframe.poses.iter().find(|p| p.name == name).unwrap()
+    }
+
+    #[test]
+    fn test_include_subtrees_and_exclude_bones() {
+        let infos = HashMap::from([
+            (
+                "Armature".to_string(),
+                node(None, Quat::IDENTITY, Vec3::ZERO),
+            ),
+            (
+                "Hips".to_string(),
+                node(Some("Armature"), Quat::IDENTITY, Vec3::ZERO),
+            ),
+            (
+                "Spine".to_string(),
+                node(Some("Hips"), Quat::IDENTITY, Vec3::ZERO),
+            ),
+            (
+                "Head_end".to_string(),
+                node(Some("Spine"), Quat::IDENTITY, Vec3::ZERO),
+            ),
+            (
+                "Prop".to_string(),
+                node(Some("Armature"), Quat::IDENTITY, Vec3::ZERO),
+            ),
+        ]);
+
+        let selection = BoneSelection::new()
+            .include("Hips")
+            .unwrap()
+            .exclude("*_end")
+            .unwrap();
+        assert!(!selection.keeps("Armature", &infos));
+        assert!(selection.keeps("Spine", &infos));
+        assert!(!selection.keeps("Head_end", &infos));
+        assert!(!selection.keeps("Prop", &infos));
+
+        let selected = selection.apply_to_bone_infos(&infos);
+        assert_eq!(selected.len(), 2);
+        assert!(selected["Hips"].parent.is_none());
+
+        let regex = BoneSelection::new().exclude("re:^(Prop|Spine)$").unwrap();
+        assert!(!regex.keeps("Spine", &infos));
+        assert!(regex.keeps("Head_end", &infos));
+        assert_eq!(
+            regex.apply_to_bone_infos(&infos)["Head_end"]
+                .parent
+                .as_deref(),
+            Some("Hips")
+        );
+        assert!(BoneSelection::new().exclude("re:(").is_err());
+
+        let class = BoneSelection::new().exclude("[PS]*").unwrap();
+        assert!(!class.keeps("Prop", &infos));
+        assert!(!class.keeps("Spine", &infos));
+        assert!(class.keeps("Hips", &infos));
+        assert!(BoneSelection::new().exclude("[").is_err());
+    }
+
+    #[test]
+    fn test_excluded_animation_is_baked_into_children() {
+        let infos = HashMap::from([
+            (
+                "Root".to_string(),
+                node(None, Quat::IDENTITY, Vec3::new(0.0, 1.0, 0.0)),
+            ),
+            (
+                "Twist".to_string(),
+                node(
+                    Some("Root"),
+                    Quat::from_rotation_y(1.2),
+                    Vec3::new(1.0, 0.0, 0.0),
+                ),
+            ),
+            (
+                "Hand".to_string(),
+                node(
+                    Some("Twist"),
+                    Quat::from_rotation_x(0.4),
+                    Vec3::new(0.0, 0.0, 1.0),
+                ),
+            ),
+        ]);
+        let pose = |name: &str, position: Vec3, rotation: Quat| {
+            Pose::new(name.to_string(), cframe_from_glam(position, rotation))
+        };
+        let original = vec![
+            Keyframe {
+                time: 0.0,
+                poses: vec![
+                    pose("Twist", Vec3::ZERO, Quat::from_rotation_z(0.5)),
+                    pose("Hand", Vec3::new(0.1, 0.0, 0.0), Quat::IDENTITY),
+                ],
+            },
+            Keyframe {
+                time: 1.0,
+                poses: vec![pose("Twist", Vec3::ZERO, Quat::from_rotation_z(-0.5))],
+            },
+        ];
+
+        let selection = BoneSelection::new().exclude("Twist").unwrap();
+        let mut keyframes = original.clone();
+        let removed = selection.apply_to_keyframes(&mut keyframes, &infos);
+        let selected = selection.apply_to_bone_infos(&infos);
+
+        assert_eq!(removed, vec!["Twist".to_string()]);
+        assert_eq!(selected["Hand"].parent.as_deref(), Some("Root"));
+        assert_eq!(keyframes.len(), 2);
+
+        // The hand keeps its transform relative to the root at every keyframe
+        for (index, keyframe) in keyframes.iter().enumerate() {
+            let twist = animated(&infos["Twist"], pose_of(&original[index], "Twist"));
+            let hand = animated(&infos["Hand"], pose_of(&original[0], "Hand"));
+            let expected = twist * hand;
+            let actual = animated(&selected["Hand"], pose_of(keyframe, "Hand"));
+            assert!(expected.abs_diff_eq(actual, 1e-4));
+        }
+    }
+}
+
+mod hierarchy_tests {
+    use anim2rbx::rest_relative_pose;
+    use anim2rbx::utils::mat4_to_matrix4x4;
+    use glam::{Mat4, Quat, Vec3};
+
+    #[test]
+    fn test_rest_relative_pose_without_offset() {
+        let rest =
+            Mat4::from_rotation_translation(Quat::from_rotation_x(0.3), Vec3::new(0.0, 2.0, 0.0));
+        let (position, rotation) = rest_relative_pose(
+            &mat4_to_matrix4x4(&rest),
+            Mat4::IDENTITY,
+            Some(Vec3::new(0.5, 2.0, 0.0)),
+            Some(Quat::from_rotation_x(0.5)),
+        );
+
+        assert!(position.distance(Vec3::new(0.5, 0.0, 0.0)) < 1e-5);
+        assert!(rotation.angle_between(Quat::from_rotation_x(0.2)) < 1e-3);
+    }
+
+    #[test]
+    fn test_static_parent_offset_is_baked() {
+        // An unanimated pivot rotated a quarter turn about Y sits between two bones
+        let offset = Mat4::from_rotation_translation(
+            Quat::from_rotation_y(std::f32::consts::FRAC_PI_2),
+            Vec3::new(0.0, 0.0, 3.0),
+        );
+        let local_rest = Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0));
+        let rest = mat4_to_matrix4x4(&(offset * local_rest));
+
+        let (position, rotation) = rest_relative_pose(
+            &rest,
+            offset,
+            Some(Vec3::new(2.0, 0.0, 0.0)),
+            Some(Quat::from_rotation_z(0.4)),
+        );
+
+        // Moving along the node's local X moves along the parent's -Z
+        assert!(position.distance(Vec3::new(0.0, 0.0, -1.0)) < 1e-5);
+        assert!(rotation.angle_between(Quat::from_rotation_z(0.4)) < 1e-3);
+
+        let (position, rotation) = rest_relative_pose(&rest, offset, None, None);
+        assert_eq!(position, Vec3::ZERO);
+        assert_eq!(rotation, Quat::IDENTITY);
+    }
+}
+
+mod fbx_helper_tests {
+    use anim2rbx::utils::{
+        cframe_to_glam, fbx_helper_owner, fold_fbx_helper_infos, fold_fbx_helpers,
+    };
+    use glam::{Quat, Vec3};
+
+    use crate::helpers::{hierarchy, keyframe};
+
+    #[test]
+    fn test_helper_owner() {
+        assert_eq!(fbx_helper_owner("Arm_$AssimpFbx$_PreRotation"), Some("Arm"));
+        assert_eq!(fbx_helper_owner("Arm"), None);
+    }
+
+    #[test]
+    fn test_helpers_fold_into_owner() {
+        let translation = "Arm_$AssimpFbx$_Translation";
+        let rotation = "Arm_$AssimpFbx$_Rotation";
+        let infos = hierarchy(&[
+            ("Spine", None, Quat::IDENTITY, Vec3::ZERO),
+            (translation, Some("Spine"), Quat::IDENTITY, Vec3::X),
+            (rotation, Some(translation), Quat::IDENTITY, Vec3::ZERO),
+            ("Arm", Some(rotation), Quat::IDENTITY, Vec3::ZERO),
+            ("Hand", Some("Arm"), Quat::IDENTITY, Vec3::NEG_Y),
+        ]);
+        let mut keyframes = vec![keyframe(
+            0.0,
+            &[
+                (translation, Vec3::new(0.0, 0.5, 0.0), Quat::IDENTITY),
+                (rotation, Vec3::ZERO, Quat::from_rotation_z(0.3)),
+                ("Hand", Vec3::ZERO, Quat::from_rotation_x(0.2)),
+            ],
+        )];
+
+        let folded = fold_fbx_helpers(&mut keyframes, &infos);
+        let folded_infos = fold_fbx_helper_infos(&infos);
+
+        assert_eq!(folded, vec![rotation.to_string(), translation.to_string()]);
+        assert_eq!(folded_infos.len(), 3);
+        assert_eq!(folded_infos["Arm"].parent.as_deref(), Some("Spine"));
+        assert_eq!(folded_infos["Hand"].parent.as_deref(), Some("Arm"));
+
+        let mut names: Vec<_> = keyframes[0].poses.iter().map(|p| p.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["Arm", "Hand"]);
+
+        // The owner carries both helpers' channels
+        let arm = keyframes[0].poses.iter().find(|p| p.name == "Arm").unwrap();
+        let (position, rotation) = cframe_to_glam(&arm.cframe);
+        assert!(position.distance(Vec3::new(0.0, 0.5, 0.0)) < 1e-5);
+        assert!(rotation.angle_between(Quat::from_rotation_z(0.3)) < 1e-3);
+    }
+}
+
+mod mirror_tests {
+    use std::collections::HashMap;
+
+    use anim2rbx::mirror::{MirrorConfig, mirror_keyframes};
+    use anim2rbx::utils::{cframe_from_glam, cframe_to_glam, quat_angle_between};
+    use anim2rbx::{Keyframe, NodeInfo, Pose};
+    use glam::{Quat, Vec3};
+
+    use crate::helpers::{hierarchy, keyframe, node};
+
+    fn skeleton() -> HashMap<String, NodeInfo> {
+        // The arms' rest axes are deliberately not mirror images of each other
+        hierarchy(&[
+            ("Hips", None, Quat::IDENTITY, Vec3::ZERO),
+            ("LeftArm", Some("Hips"), Quat::from_rotation_z(1.2), Vec3::X),
+            (
+                "RightArm",
+                Some("Hips"),
+                Quat::from_rotation_y(0.7),
+                Vec3::NEG_X,
+            ),
+        ])
+    }
+
+    fn clip() -> Vec<Keyframe> {
+        vec![keyframe(
+            0.0,
+            &[
+                ("Hips", Vec3::new(1.0, 0.0, 2.0), Quat::IDENTITY),
+                ("LeftArm", Vec3::ZERO, Quat::from_rotation_x(0.5)),
+            ],
+        )]
+    }
+
+    #[test]
+    fn test_counterpart_names() {
+        let config = MirrorConfig::default();
+        assert_eq!(
+            config.counterpart_name("mixamorig:LeftHand").as_deref(),
+            Some("mixamorig:RightHand")
+        );
+        assert_eq!(
+            config.counterpart_name("Right Arm").as_deref(),
+            Some("Left Arm")
+        );
+        assert_eq!(
+            config.counterpart_name("thigh.L").as_deref(),
+            Some("thigh.R")
+        );
+        assert_eq!(config.counterpart_name("Spine"), None);
+    }
+
+    #[test]
+    fn test_counterpart_fragment_inside_name() {
+        let config = MirrorConfig::default();
+        assert_eq!(
+            config.counterpart_name("leg_lower_l").as_deref(),
+            Some("leg_lower_r")
+        );
+        assert_eq!(
+            config.counterpart_candidates("leg_lower_l"),
+            vec!["leg_lower_r", "leg_rower_l"]
+        );
+
+        // Only candidates that are bones count, whichever swap produces them
+        let infos = HashMap::from([
+            ("Hips".to_string(), node(None, Quat::IDENTITY, Vec3::ZERO)),
+            (
+                "hand_l_ik".to_string(),
+                node(Some("Hips"), Quat::IDENTITY, Vec3::X),
+            ),
+            (
+                "hand_r_ik".to_string(),
+                node(Some("Hips"), Quat::IDENTITY, Vec3::NEG_X),
+            ),
+        ]);
+        let mut keyframes = vec![Keyframe {
+            time: 0.0,
+            poses: vec![Pose::new(
+                "hand_l_ik".to_string(),
+                cframe_from_glam(Vec3::ZERO, Quat::from_rotation_x(0.5)),
+            )],
+        }];
+        mirror_keyframes(&mut keyframes, &infos, &config);
+
+        let names: Vec<_> = keyframes[0].poses.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["hand_r_ik"]);
+    }
+
+    #[test]
+    fn test_counterparts_are_symmetric() {
+        let config = MirrorConfig::default();
+        let infos = hierarchy(&[
+            ("Hips", None, Quat::IDENTITY, Vec3::ZERO),
+            ("Left_L", Some("Hips"), Quat::IDENTITY, Vec3::X),
+            ("Right_L", Some("Hips"), Quat::IDENTITY, Vec3::NEG_X),
+            ("Left_R", Some("Hips"), Quat::IDENTITY, Vec3::Y),
+        ]);
+
+        // Left_R's best match is Left_L, which pairs with Right_L instead
+        let counterparts = config.counterparts(&infos);
+        assert_eq!(counterparts["Left_L"], "Right_L");
+        assert_eq!(counterparts["Right_L"], "Left_L");
+        assert_eq!(counterparts["Left_R"], "Left_R");
+        assert_eq!(counterparts["Hips"], "Hips");
+        for (name, counterpart) in &counterparts {
+            assert_eq!(counterparts[counterpart], *name);
+        }
+    }
+
+    #[test]
+    fn test_mirror_reflects_relative_to_rest() {
+        let infos = skeleton();
+        let mut keyframes = clip();
+        mirror_keyframes(&mut keyframes, &infos, &MirrorConfig::default());
+
+        let hips = keyframes[0]
+            .poses
+            .iter()
+            .find(|p| p.name == "Hips")
+            .unwrap();
+        let (position, _) = cframe_to_glam(&hips.cframe);
+        assert!(position.distance(Vec3::new(-1.0, 0.0, 2.0)) < 1e-5);
+
+        assert!(keyframes[0].poses.iter().all(|p| p.name != "LeftArm"));
+        let right = keyframes[0]
+            .poses
+            .iter()
+            .find(|p| p.name == "RightArm")
+            .unwrap();
+        let (_, delta) = cframe_to_glam(&right.cframe);
+
+        // The arm's model-space change from rest is the reflection of the left arm's
+        let left_rest = Quat::from_rotation_z(1.2);
+        let right_rest = Quat::from_rotation_y(0.7);
+        let left_change = left_rest * Quat::from_rotation_x(0.5) * left_rest.inverse();
+        let reflected =
+            Quat::from_xyzw(left_change.x, -left_change.y, -left_change.z, left_change.w);
+        let right_change = right_rest * delta * right_rest.inverse();
+        assert!(quat_angle_between(right_change, reflected).to_degrees() < 1e-3);
+    }
+
+    #[test]
+    fn test_mirror_twice_restores_clip() {
+        let infos = skeleton();
+        let mut keyframes = clip();
+        mirror_keyframes(&mut keyframes, &infos, &MirrorConfig::default());
+        mirror_keyframes(&mut keyframes, &infos, &MirrorConfig::default());
+
+        for (pose, original) in keyframes[0].poses.iter().zip(&clip()[0].poses) {
+            assert_eq!(pose.name, original.name);
+            let (position, rotation) = cframe_to_glam(&pose.cframe);
+            let (original_position, original_rotation) = cframe_to_glam(&original.cframe);
+            assert!(position.distance(original_position) < 1e-4);
+            assert!(quat_angle_between(rotation, original_rotation).to_degrees() < 1e-3);
+        }
+    }
+}
+
+mod additive_tests {
+    use std::collections::HashMap;
+
+    use anim2rbx::Keyframe;
+    use anim2rbx::additive::{AdditiveReference, make_additive, reference_pose};
+    use anim2rbx::utils::{cframe_to_glam, quat_angle_between};
+    use glam::{Quat, Vec3};
+
+    use crate::helpers::keyframe;
+
+    fn clip() -> Vec<Keyframe> {
+        vec![
+            keyframe(
+                0.0,
+                &[
+                    ("Hips", Vec3::new(0.0, 1.0, 0.0), Quat::from_rotation_y(0.3)),
+                    ("Spine", Vec3::ZERO, Quat::from_rotation_x(0.2)),
+                ],
+            ),
+            keyframe(
+                1.0,
+                &[
+                    (
+                        "Hips",
+                        Vec3::new(1.0, 1.5, 0.0),
+                        Quat::from_rotation_y(0.3) * Quat::from_rotation_z(0.4),
+                    ),
+                    ("Spine", Vec3::ZERO, Quat::from_rotation_x(0.6)),
+                ],
+            ),
+        ]
+    }
+
+    fn assert_pose(keyframe: &Keyframe, name: &str, position: Vec3, rotation: Quat) {
+        let pose = keyframe.poses.iter().find(|p| p.name == name).unwrap();
+        let (actual_position, actual_rotation) = cframe_to_glam(&pose.cframe);
+        assert!(actual_position.distance(position) < 1e-5, "{}", name);
+        assert!(
+            quat_angle_between(actual_rotation, rotation).to_degrees() < 1e-3,
+            "{}",
+            name
+        );
+    }
+
+    #[test]
+    fn test_first_frame_reference() {
+        let mut keyframes = clip();
+        make_additive(&mut keyframes, &AdditiveReference::default());
+
+        assert_pose(&keyframes[0], "Hips", Vec3::ZERO, Quat::IDENTITY);
+        assert_pose(&keyframes[0], "Spine", Vec3::ZERO, Quat::IDENTITY);
+        assert_pose(
+            &keyframes[1],
+            "Hips",
+            Vec3::new(1.0, 0.5, 0.0),
+            Quat::from_rotation_z(0.4),
+        );
+        assert_pose(
+            &keyframes[1],
+            "Spine",
+            Vec3::ZERO,
+            Quat::from_rotation_x(0.4),
+        );
+    }
+
+    #[test]
+    fn test_chosen_frame_reference() {
+        let mut keyframes = clip();
+        make_additive(&mut keyframes, &AdditiveReference::Frame(1));
+
+        assert_pose(&keyframes[1], "Spine", Vec3::ZERO, Quat::IDENTITY);
+        assert_pose(
+            &keyframes[0],
+            "Spine",
+            Vec3::ZERO,
+            Quat::from_rotation_x(-0.4),
+        );
+    }
+
+    #[test]
+    fn test_reference_poses_from_other_clip() {
+        let reference = reference_pose(&clip()[..1], 0);
+        assert_eq!(reference.len(), 2);
+        assert!(reference_pose(&clip(), 5).is_empty());
+
+        // Bones missing from the reference keep their absolute poses
+        let reference: HashMap<_, _> = reference
+            .into_iter()
+            .filter(|(name, _)| name == "Hips")
+            .collect();
+        let mut keyframes = clip();
+        make_additive(&mut keyframes, &AdditiveReference::Poses(reference));
+
+        assert_pose(&keyframes[0], "Hips", Vec3::ZERO, Quat::IDENTITY);
+        assert_pose(
+            &keyframes[1],
+            "Spine",
+            Vec3::ZERO,
+            Quat::from_rotation_x(0.6),
+        );
+    }
+}
+
+mod rest_pose_tests {
+    use std::collections::HashMap;
+
+    use anim2rbx::NodeInfo;
+    use anim2rbx::converter::rest_relative_pose;
+    use anim2rbx::rest_pose::{apply_global_rest_transforms, apply_local_rest_transforms};
+    use anim2rbx::utils::{decompose_matrix4x4, quat_angle_between};
+    use glam::{Mat4, Quat, Vec3};
+
+    use crate::helpers::hierarchy;
+
+    fn skeleton() -> HashMap<String, NodeInfo> {
+        hierarchy(&[
+            ("Hips", None, Quat::IDENTITY, Vec3::new(0.0, 1.0, 0.0)),
+            (
+                "Spine",
+                Some("Hips"),
+                Quat::IDENTITY,
+                Vec3::new(0.0, 0.5, 0.0),
+            ),
+            (
+                "Head",
+                Some("Spine"),
+                Quat::IDENTITY,
+                Vec3::new(0.0, 0.5, 0.0),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_local_rest_transforms_keep_parent_offset() {
+        let mut infos = skeleton();
+        let offsets = HashMap::from([("Spine".to_string(), Mat4::from_rotation_y(1.0))]);
+        let locals = HashMap::from([(
+            "Spine".to_string(),
+            Mat4::from_rotation_translation(Quat::from_rotation_x(0.3), Vec3::Y),
+        )]);
+        apply_local_rest_transforms(&mut infos, &offsets, &locals);
+
+        let (translation, rotation) = decompose_matrix4x4(&infos["Spine"].rest_transform);
+        // A rotation about Y keeps the translation along Y
+        assert!(translation.distance(Vec3::Y) < 1e-5);
+        let expected = Quat::from_rotation_y(1.0) * Quat::from_rotation_x(0.3);
+        assert!(quat_angle_between(rotation, expected).to_degrees() < 1e-3);
+
+        // The key that matches the new rest is the identity pose
+        let (position, delta) = rest_relative_pose(
+            &infos["Spine"].rest_transform,
+            offsets["Spine"],
+            Some(Vec3::Y),
+            Some(Quat::from_rotation_x(0.3)),
+        );
+        assert!(position.length() < 1e-5);
+        assert!(quat_angle_between(delta, Quat::IDENTITY).to_degrees() < 1e-3);
+
+        // Bones without an override are untouched
+        let (translation, _) = decompose_matrix4x4(&infos["Head"].rest_transform);
+        assert!(translation.distance(Vec3::new(0.0, 0.5, 0.0)) < 1e-5);
+    }
+
+    #[test]
+    fn test_global_rest_transforms_become_local() {
+        let mut infos = skeleton();
+        // A bind pose with the spine bent forward, and no bind pose for the head
+        let globals = HashMap::from([
+            (
+                "Hips".to_string(),
+                Mat4::from_translation(Vec3::new(0.0, 2.0, 0.0)),
+            ),
+            (
+                "Spine".to_string(),
+                Mat4::from_rotation_translation(
+                    Quat::from_rotation_x(0.5),
+                    Vec3::new(0.0, 2.5, 0.0),
+                ),
+            ),
+        ]);
+        apply_global_rest_transforms(&mut infos, &globals);
+
+        let (translation, _) = decompose_matrix4x4(&infos["Hips"].rest_transform);
+        assert!(translation.distance(Vec3::new(0.0, 2.0, 0.0)) < 1e-5);
+
+        let (translation, rotation) = decompose_matrix4x4(&infos["Spine"].rest_transform);
+        assert!(translation.distance(Vec3::new(0.0, 0.5, 0.0)) < 1e-5);
+        assert!(quat_angle_between(rotation, Quat::from_rotation_x(0.5)).to_degrees() < 1e-3);
+
+        let (translation, rotation) = decompose_matrix4x4(&infos["Head"].rest_transform);
+        assert!(translation.distance(Vec3::new(0.0, 0.5, 0.0)) < 1e-5);
+        assert!(quat_angle_between(rotation, Quat::IDENTITY).to_degrees() < 1e-3);
+    }
+}
+
+mod timing_tests {
+    use anim2rbx::timing::{TimeOperation, ping_pong, reverse, scale_time, stretch_to_duration};
+    use anim2rbx::utils::cframe_from_glam;
+    use anim2rbx::{EasingDirection, EasingStyle, Keyframe, Pose};
+    use glam::{Quat, Vec3};
+
+    fn clip() -> Vec<Keyframe> {
+        let easings = [
+            (EasingStyle::Cubic, EasingDirection::In),
+            (EasingStyle::Bounce, EasingDirection::InOut),
+            (EasingStyle::Linear, EasingDirection::Out),
+        ];
+        easings
+            .iter()
+            .enumerate()
+            .map(|(index, &(style, direction))| {
+                let pose = Pose::new(
+                    "Arm",
+                    cframe_from_glam(Vec3::new(index as f32, 0.0, 0.0), Quat::IDENTITY),
+                )
+                .with_easing(style, direction);
+                Keyframe {
+                    time: index as f64 * 0.5,
+                    poses: vec![pose],
+                }
+            })
+            .collect()
+    }
+
+    fn times(keyframes: &[Keyframe]) -> Vec<f64> {
+        keyframes.iter().map(|kf| kf.time).collect()
+    }
+
+    fn x_positions(keyframes: &[Keyframe]) -> Vec<f32> {
+        keyframes
+            .iter()
+            .map(|kf| kf.poses[0].cframe.position.x)
+            .collect()
+    }
+
+    #[test]
+    fn test_scale_and_stretch() {
+        let mut keyframes = clip();
+        scale_time(&mut keyframes, 2.0);
+        assert_eq!(times(&keyframes), vec![0.0, 1.0, 2.0]);
+
+        stretch_to_duration(&mut keyframes, 0.5);
+        assert_eq!(times(&keyframes), vec![0.0, 0.25, 0.5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "positive and finite")]
+    fn test_scale_rejects_non_positive_factors() {
+        TimeOperation::Scale(-1.0).apply(&mut clip());
+    }
+
+    #[test]
+    #[should_panic(expected = "positive and finite")]
+    fn test_scale_rejects_non_finite_factors() {
+        scale_time(&mut clip(), f64::NAN);
+    }
+
+    #[test]
+    fn test_reverse_moves_and_swaps_easing() {
+        let mut keyframes = clip();
+        reverse(&mut keyframes);
+
+        assert_eq!(times(&keyframes), vec![0.0, 0.5, 1.0]);
+        assert_eq!(x_positions(&keyframes), vec![2.0, 1.0, 0.0]);
+
+        // The segment 2 -> 1 was authored as 1 -> 2 with Bounce InOut on pose 1
+        let easings: Vec<_> = keyframes
+            .iter()
+            .map(|kf| (kf.poses[0].easing_style, kf.poses[0].easing_direction))
+            .collect();
+        assert_eq!(easings[0], (EasingStyle::Bounce, EasingDirection::InOut));
+        assert_eq!(easings[1], (EasingStyle::Cubic, EasingDirection::Out));
+    }
+
+    fn stepped(styles: &[EasingStyle]) -> Vec<Keyframe> {
+        styles
+            .iter()
+            .enumerate()
+            .map(|(index, &style)| Keyframe {
+                time: index as f64,
+                poses: vec![
+                    Pose::new(
+                        "Arm",
+                        cframe_from_glam(Vec3::new(index as f32, 0.0, 0.0), Quat::IDENTITY),
+                    )
+                    .with_easing(style, EasingDirection::In),
+                ],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_reverse_keeps_constant_segments_holding_their_value() {
+        use EasingStyle::{Constant, Linear};
+
+        // 0 is held for a second, then eases into 2; the held 0 is baked after the ease ends
+        let mut keyframes = stepped(&[Constant, Linear, Linear]);
+        reverse(&mut keyframes);
+        assert_eq!(times(&keyframes), vec![0.0, 1.0, 1.001, 2.0]);
+        assert_eq!(x_positions(&keyframes), vec![2.0, 1.0, 0.0, 0.0]);
+        let styles: Vec<_> = keyframes
+            .iter()
+            .map(|kf| kf.poses[0].easing_style)
+            .collect();
+        assert_eq!(styles[..3], [Linear, Constant, Constant]);
+
+        // Steps that follow steps just move the held value
+        let mut keyframes = stepped(&[Constant, Constant, Linear]);
+        reverse(&mut keyframes);
+        assert_eq!(times(&keyframes), vec![0.0, 1.0, 2.0]);
+        assert_eq!(x_positions(&keyframes), vec![1.0, 0.0, 0.0]);
+        assert_eq!(keyframes[0].poses[0].easing_style, Constant);
+        assert_eq!(keyframes[1].poses[0].easing_style, Constant);
+    }
+
+    #[test]
+    fn test_ping_pong() {
+        let mut keyframes = clip();
+        ping_pong(&mut keyframes);
+
+        assert_eq!(times(&keyframes), vec![0.0, 0.5, 1.0, 1.5, 2.0]);
+        assert_eq!(x_positions(&keyframes), vec![0.0, 1.0, 2.0, 1.0, 0.0]);
+        assert_eq!(keyframes[1].poses[0].easing_style, EasingStyle::Bounce);
+        assert_eq!(keyframes[2].poses[0].easing_style, EasingStyle::Bounce);
+        assert_eq!(keyframes[3].poses[0].easing_direction, EasingDirection::Out);
+    }
+
+    #[test]
+    fn test_reverse_twice_restores_clip() {
+        let mut keyframes = clip();
+        TimeOperation::Reverse.apply(&mut keyframes);
+        TimeOperation::Reverse.apply(&mut keyframes);
+
+        assert_eq!(x_positions(&keyframes), x_positions(&clip()));
+        for (keyframe, original) in keyframes.iter().zip(&clip()).take(2) {
+            assert_eq!(
+                keyframe.poses[0].easing_style,
+                original.poses[0].easing_style
+            );
+            assert_eq!(
+                keyframe.poses[0].easing_direction,
+                original.poses[0].easing_direction
+            );
+        }
+    }
+}
+
+mod clips_tests {
+    use anim2rbx::clips::{ClipSource, ConcatConfig, concatenate};
+    use anim2rbx::utils::{cframe_from_glam, cframe_to_glam, quat_angle_between};
+    use anim2rbx::{Keyframe, Pose};
+    use glam::{Quat, Vec3};
+
+    fn clip(bone: &str, start: f64, angles: &[f32]) -> Vec<Keyframe> {
+        angles
+            .iter()
+            .enumerate()
+            .map(|(index, &angle)| Keyframe {
+                time: start + index as f64,
+                poses: vec![Pose::new(
+                    bone,
+                    cframe_from_glam(Vec3::ZERO, Quat::from_rotation_x(angle)),
+                )],
+            })
+            .collect()
+    }
+
+    fn angle_of(keyframe: &Keyframe, bone: &str) -> f32 {
+        let pose = keyframe.poses.iter().find(|p| p.name == bone).unwrap();
+        let (_, rotation) = cframe_to_glam(&pose.cframe);
+        quat_angle_between(rotation, Quat::IDENTITY)
+    }
+
+    fn times(keyframes: &[Keyframe]) -> Vec<f64> {
+        keyframes.iter().map(|kf| kf.time).collect()
+    }
+
+    #[test]
+    fn test_clip_source_parse() {
+        assert_eq!(
+            ClipSource::parse("combo.fbx#Slash"),
+            ClipSource {
+                path: "combo.fbx".to_string(),
+                animation: Some("Slash".to_string()),
+            }
+        );
+        assert_eq!(ClipSource::parse("kick.fbx").animation, None);
+        assert_eq!(
+            ClipSource::parse("combo.fbx#Slash").to_string(),
+            "combo.fbx#Slash"
+        );
+    }
+
+    #[test]
+    fn test_concatenate_with_gap() {
+        let clips = [clip("Arm", 2.0, &[0.0, 0.5]), clip("Arm", 0.0, &[1.0, 0.2])];
+        let joined = concatenate(
+            &clips,
+            &ConcatConfig {
+                gap: 0.5,
+                crossfade: 0.0,
+            },
+        );
+        assert_eq!(times(&joined), vec![0.0, 1.0, 1.5, 2.5]);
+        assert!((angle_of(&joined[2], "Arm") - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_hard_cut_replaces_shared_time() {
+        let clips = [clip("Arm", 0.0, &[0.0, 0.5]), clip("Arm", 0.0, &[1.0, 0.2])];
+        let joined = concatenate(&clips, &ConcatConfig::default());
+        assert_eq!(times(&joined), vec![0.0, 1.0, 2.0]);
+        assert!((angle_of(&joined[1], "Arm") - 1.0).abs() < 1e-4);
+        assert_eq!(joined[1].poses.len(), 1);
+    }
+
+    #[test]
+    fn test_crossfade_blends_and_rests_missing_bones() {
+        let clips = [
+            clip("Arm", 0.0, &[0.0, 0.0, 0.0, 0.0, 0.0]),
+            clip("Leg", 0.0, &[0.8, 0.8, 0.8]),
+        ];
+        let joined = concatenate(
+            &clips,
+            &ConcatConfig {
+                gap: 0.0,
+                crossfade: 2.0,
+            },
+        );
+        assert_eq!(times(&joined), vec![0.0, 1.0, 2.0, 3.0, 4.0]);
+
+        // The leg fades in from rest across the overlap
+        assert!(angle_of(&joined[0], "Leg") < 1e-4);
+        assert!(angle_of(&joined[2], "Leg") < 1e-4);
+        assert!((angle_of(&joined[3], "Leg") - 0.4).abs() < 1e-4);
+        assert!((angle_of(&joined[4], "Leg") - 0.8).abs() < 1e-4);
+
+        // Every bone is posed at both ends of every clip
+        assert!(joined[4].poses.iter().any(|p| p.name == "Arm"));
+        assert!(angle_of(&joined[4], "Arm") < 1e-4);
+    }
+
+    #[test]
+    fn test_crossfade_as_long_as_gap() {
+        let clips = [
+            clip("Arm", 0.0, &[0.0, 0.0]),
+            clip("Arm", 0.0, &[0.8, 0.8, 0.8]),
+        ];
+        let joined = concatenate(
+            &clips,
+            &ConcatConfig {
+                gap: 2.0,
+                crossfade: 2.0,
+            },
+        );
+        assert_eq!(times(&joined), vec![0.0, 1.0, 2.0, 3.0]);
+
+        // The fade starts from the first clip's held last pose and still spans its length
+        let angles: Vec<f32> = joined.iter().map(|kf| angle_of(kf, "Arm")).collect();
+        assert!(angles.iter().all(|angle| angle.is_finite()));
+        assert!(angles[1] < 1e-4);
+        assert!((angles[2] - 0.4).abs() < 1e-4);
+        assert!((angles[3] - 0.8).abs() < 1e-4);
+    }
+}
+
+mod split_tests {
+    use anim2rbx::clips::{ClipRanges, slice_keyframes};
+    use anim2rbx::utils::{cframe_from_glam, cframe_to_glam};
+    use anim2rbx::{Keyframe, Pose};
+    use glam::{Quat, Vec3};
+
+    fn take() -> Vec<Keyframe> {
+        (0..=4)
+            .map(|index| Keyframe {
+                time: index as f64,
+                poses: vec![Pose::new(
+                    "Hips",
+                    cframe_from_glam(Vec3::new(index as f32, 0.0, 0.0), Quat::IDENTITY),
+                )],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ranges_from_toml_and_json() {
+        let ranges = ClipRanges::from_toml_str(
+            r#"
+            fps = 30
+
+            [[clips]]
+            name = "Jab"
+            start = 1.25
+            end = 2.0
+
+            [[clips]]
+            name = "Kick"
+            start_frame = 90
+            end_frame = 150
+            "#,
+        )
+        .unwrap();
+        assert_eq!(ranges.ranges.len(), 2);
+        assert_eq!(ranges.ranges[0].name, "Jab");
+        assert_eq!(ranges.ranges[1].start, 3.0);
+        assert_eq!(ranges.ranges[1].end, 5.0);
+
+        let json = ClipRanges::from_json_str(
+            r#"{ "clips": [{ "name": "Idle", "start": 0.0, "end": 1.0 }] }"#,
+        )
+        .unwrap();
+        assert_eq!(json.ranges[0].end, 1.0);
+    }
+
+    #[test]
+    fn test_invalid_ranges() {
+        // Frames need an fps, and clips must not run backwards
+        assert!(
+            ClipRanges::from_toml_str("[[clips]]\nname = \"A\"\nstart_frame = 1\nend_frame = 2")
+                .is_err()
+        );
+        assert!(ClipRanges::from_toml_str("[[clips]]\nname = \"A\"\nstart = 2\nend = 1").is_err());
+    }
+
+    #[test]
+    fn test_slice_is_rezeroed_and_interpolated() {
+        let slice = slice_keyframes(&take(), 1.5, 3.25);
+        let times: Vec<f64> = slice.iter().map(|kf| kf.time).collect();
+        assert_eq!(times, vec![0.0, 0.5, 1.5, 1.75]);
+
+        let x = |keyframe: &Keyframe| cframe_to_glam(&keyframe.poses[0].cframe).0.x;
+        assert!((x(&slice[0]) - 1.5).abs() < 1e-5);
+        assert!((x(&slice[1]) - 2.0).abs() < 1e-5);
+        assert!((x(&slice[3]) - 3.25).abs() < 1e-5);
+    }
+}
+
+mod smoothing_tests {
+    use std::collections::HashMap;
+
+    use anim2rbx::smoothing::{SmoothingConfig, smooth_keyframes};
+    use anim2rbx::utils::{cframe_from_glam, cframe_to_glam, quat_angle_between};
+    use anim2rbx::{Keyframe, Pose, Tolerance};
+    use glam::{Quat, Vec3};
+
+    fn track(bone: &str, samples: &[(f32, f32)]) -> Vec<Keyframe> {
+        samples
+            .iter()
+            .enumerate()
+            .map(|(index, &(x, angle))| Keyframe {
+                time: index as f64 / 30.0,
+                poses: vec![Pose::new(
+                    bone,
+                    cframe_from_glam(Vec3::new(x, 0.0, 0.0), Quat::from_rotation_y(angle)),
+                )],
+            })
+            .collect()
+    }
+
+    fn sample(keyframe: &Keyframe) -> (Vec3, Quat) {
+        cframe_to_glam(&keyframe.poses[0].cframe)
+    }
+
+    #[test]
+    fn test_spike_is_repaired() {
+        let mut keyframes = track(
+            "Hand",
+            &[(0.0, 0.0), (0.1, 0.1), (3.0, 2.0), (0.3, 0.3), (0.4, 0.4)],
+        );
+        let config = SmoothingConfig {
+            spike_tolerance: Some(Tolerance::new(0.5, 30.0)),
+            ..Default::default()
+        };
+        let report = smooth_keyframes(&mut keyframes, &config);
+
+        assert_eq!(report.spikes.len(), 1);
+        assert_eq!(report.spikes[0].bone, "Hand");
+        let (position, rotation) = sample(&keyframes[2]);
+        assert!((position.x - 0.2).abs() < 1e-5);
+        assert!(quat_angle_between(rotation, Quat::from_rotation_y(0.2)) < 1e-4);
+    }
+
+    #[test]
+    fn test_fast_motion_is_not_a_spike() {
+        let mut keyframes = track("Hand", &[(0.0, 0.0), (2.0, 1.0), (4.0, 2.0), (6.0, 3.0)]);
+        let config = SmoothingConfig {
+            spike_tolerance: Some(Tolerance::new(0.5, 30.0)),
+            ..Default::default()
+        };
+        assert!(smooth_keyframes(&mut keyframes, &config).spikes.is_empty());
+    }
+
+    #[test]
+    fn test_smoothing_reduces_jitter_per_bone() {
+        let jitter: Vec<(f32, f32)> = (0..20)
+            .map(|i| {
+                let noise = if i % 2 == 0 { 0.05 } else { -0.05 };
+                (1.0 + noise, 0.5 + noise)
+            })
+            .collect();
+        let mut keyframes = track("Spine", &jitter);
+        keyframes.extend(track("Head", &jitter).into_iter().map(|mut kf| {
+            kf.time += 0.001;
+            kf
+        }));
+
+        let config = SmoothingConfig {
+            strength: 2.0,
+            bone_strengths: HashMap::from([("Head".to_string(), 0.0)]),
+            spike_tolerance: None,
+        };
+        let report = smooth_keyframes(&mut keyframes, &config);
+        assert_eq!(report.smoothed_bones, 1);
+
+        let (position, rotation) = sample(&keyframes[10]);
+        assert_eq!(keyframes[10].poses[0].name, "Spine");
+        assert!((position.x - 1.0).abs() < 0.01);
+        assert!(quat_angle_between(rotation, Quat::from_rotation_y(0.5)) < 0.01);
+
+        let (position, _) = sample(&keyframes[30]);
+        assert_eq!(keyframes[30].poses[0].name, "Head");
+        assert!((position.x - 1.05).abs() < 1e-5);
+    }
+}
+
+mod fk_tests {
+    use std::collections::HashMap;
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    use anim2rbx::fk::{FkEvaluator, Transform, evaluate};
+    use anim2rbx::utils::get_bone_infos;
+    use anim2rbx::{AnimationConverter, Keyframe, NodeInfo};
+    use glam::{Quat, Vec3};
+
+    use crate::helpers::{hierarchy, keyframe};
+
+    /// Hips at 1 stud up, a spine above it and an arm rotated to point up
+    fn skeleton() -> HashMap<String, NodeInfo> {
+        hierarchy(&[
+            ("Hips", None, Quat::IDENTITY, Vec3::new(0.0, 1.0, 0.0)),
+            (
+                "Spine",
+                Some("Hips"),
+                Quat::IDENTITY,
+                Vec3::new(0.0, 0.5, 0.0),
+            ),
+            (
+                "Arm",
+                Some("Spine"),
+                Quat::from_rotation_z(FRAC_PI_2),
+                Vec3::new(0.5, 0.0, 0.0),
+            ),
+            (
+                "Hand",
+                Some("Arm"),
+                Quat::IDENTITY,
+                Vec3::new(1.0, 0.0, 0.0),
+            ),
+        ])
+    }
+
+    fn clip() -> Vec<Keyframe> {
+        vec![
+            keyframe(
+                0.0,
+                &[
+                    ("Hips", Vec3::ZERO, Quat::IDENTITY),
+                    ("Spine", Vec3::ZERO, Quat::IDENTITY),
+                ],
+            ),
+            keyframe(
+                1.0,
+                &[
+                    ("Hips", Vec3::new(2.0, 0.0, 0.0), Quat::IDENTITY),
+                    ("Spine", Vec3::ZERO, Quat::from_rotation_y(FRAC_PI_2)),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_rest_pose_world_positions() {
+        let infos = skeleton();
+        let transforms = evaluate(&infos, &[], 0.0);
+
+        assert!(
+            transforms["Hand"]
+                .world
+                .translation
+                .distance(Vec3::new(0.5, 2.5, 0.0))
+                < 1e-5
+        );
+        assert!(
+            transforms["Hand"]
+                .model
+                .translation
+                .distance(Vec3::new(0.5, 1.5, 0.0))
+                < 1e-5
+        );
+        assert_eq!(transforms["Hips"].model, Transform::IDENTITY);
+    }
+
+    #[test]
+    fn test_posed_world_and_model_space() {
+        let infos = skeleton();
+        let keyframes = clip();
+        let evaluator = FkEvaluator::new(&infos, &keyframes);
+
+        // Root motion moves the world transform but not the model transform
+        let halfway = evaluator.evaluate(0.5);
+        assert!((halfway["Hips"].world.translation.x - 1.0).abs() < 1e-5);
+
+        let end = evaluator.evaluate(1.0);
+        assert!(
+            end["Hand"]
+                .world
+                .translation
+                .distance(Vec3::new(2.0, 2.5, -0.5))
+                < 1e-5
+        );
+        assert!(
+            end["Hand"]
+                .model
+                .translation
+                .distance(Vec3::new(0.0, 1.5, -0.5))
+                < 1e-5
+        );
+
+        // Poses hold after the last keyframe
+        let after = evaluator.evaluate(5.0);
+        assert!(
+            after["Hand"]
+                .world
+                .translation
+                .distance(end["Hand"].world.translation)
+                < 1e-5
+        );
+    }
+
+    #[test]
+    fn test_transform_algebra() {
+        let a = Transform::new(Vec3::new(1.0, 2.0, 3.0), Quat::from_rotation_x(0.7));
+        let b = Transform::new(Vec3::new(-1.0, 0.5, 0.0), Quat::from_rotation_y(0.3));
+        let point = Vec3::new(0.2, -0.4, 1.0);
+
+        let composed = a.mul(&b).transform_point(point);
+        assert!(composed.distance(a.transform_point(b.transform_point(point))) < 1e-5);
+
+        let roundtrip = a.inverse().mul(&a);
+        assert!(roundtrip.translation.length() < 1e-5);
+        assert!(
+            Transform::from_cframe(&a.to_cframe())
+                .translation
+                .distance(a.translation)
+                < 1e-5
+        );
+    }
+
+    /// The fixture's Hips sit 1 stud up and turn a quarter turn about Y over one second,
+    /// carrying an Arm 1 stud along X
+    #[test]
+    fn test_fixture_scene_world_positions() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/turn.gltf");
+        let scene = russimp::scene::Scene::from_file(path, vec![]).unwrap();
+        let infos = get_bone_infos(&scene);
+        let keyframes = AnimationConverter::default().convert_scene_to_keyframes(&scene);
+
+        for (time, angle) in [(0.0, 0.0), (0.5, FRAC_PI_4), (1.0, FRAC_PI_2)] {
+            let transforms = evaluate(&infos, &keyframes, time);
+            let expected = Vec3::Y + Quat::from_rotation_y(angle) * Vec3::X;
+            let arm = transforms["Arm"].world.translation;
+            assert!(arm.distance(expected) < 1e-4, "{}: {}", time, arm);
+        }
+    }
+}
+
+mod sampling_tests {
+    use anim2rbx::sampling::{SequenceSampler, ease, max_deviation, sample_keyframes};
+    use anim2rbx::utils::{cframe_from_glam, cframe_to_glam};
+    use anim2rbx::{EasingDirection, EasingStyle, Keyframe, Pose};
+    use glam::{Quat, Vec3};
+
+    fn pose(name: &str, x: f32) -> Pose {
+        Pose::new(
+            name,
+            cframe_from_glam(Vec3::new(x, 0.0, 0.0), Quat::IDENTITY),
+        )
+    }
+
+    fn eased(name: &str, x: f32, style: EasingStyle, direction: EasingDirection) -> Pose {
+        pose(name, x).with_easing(style, direction)
+    }
+
+    fn x_at(keyframes: &[Keyframe], name: &str, time: f64) -> f32 {
+        cframe_to_glam(&sample_keyframes(keyframes, time)[name]).0.x
+    }
+
+    #[test]
+    fn test_ease_endpoints() {
+        let styles = [
+            EasingStyle::Linear,
+            EasingStyle::Cubic,
+            EasingStyle::Elastic,
+            EasingStyle::Bounce,
+            EasingStyle::CubicV2,
+        ];
+        let directions = [
+            EasingDirection::In,
+            EasingDirection::Out,
+            EasingDirection::InOut,
+        ];
+        for style in styles {
+            for direction in directions {
+                assert!(ease(style, direction, 0.0).abs() < 1e-5);
+                assert!((ease(style, direction, 1.0) - 1.0).abs() < 1e-5);
+            }
+        }
+        assert_eq!(ease(EasingStyle::Constant, EasingDirection::In, 0.99), 0.0);
+    }
+
+    #[test]
+    fn test_legacy_cubic_direction_is_reversed() {
+        let legacy_in = ease(EasingStyle::Cubic, EasingDirection::In, 0.5);
+        let v2_in = ease(EasingStyle::CubicV2, EasingDirection::In, 0.5);
+        assert!((v2_in - 0.125).abs() < 1e-5);
+        assert!((legacy_in - 0.875).abs() < 1e-5);
+        let in_out = ease(EasingStyle::CubicV2, EasingDirection::InOut, 0.25);
+        assert!((in_out - 0.0625).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_each_bone_interpolates_between_its_own_poses() {
+        let keyframes = vec![
+            Keyframe {
+                time: 0.0,
+                poses: vec![pose("A", 0.0), pose("B", 0.0)],
+            },
+            Keyframe {
+                time: 1.0,
+                poses: vec![pose("A", 1.0)],
+            },
+            Keyframe {
+                time: 2.0,
+                poses: vec![pose("B", 4.0)],
+            },
+        ];
+
+        assert!((x_at(&keyframes, "A", 0.5) - 0.5).abs() < 1e-5);
+        // B has no pose at 1s, so it is halfway between its poses at 0s and 2s
+        assert!((x_at(&keyframes, "B", 1.0) - 2.0).abs() < 1e-5);
+        // A holds its last pose
+        assert!((x_at(&keyframes, "A", 1.5) - 1.0).abs() < 1e-5);
+        assert!((x_at(&keyframes, "B", 5.0) - 4.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_easing_comes_from_the_earlier_pose() {
+        let keyframes = vec![
+            Keyframe {
+                time: 0.0,
+                poses: vec![eased("A", 0.0, EasingStyle::Constant, EasingDirection::In)],
+            },
+            Keyframe {
+                time: 1.0,
+                poses: vec![eased("A", 1.0, EasingStyle::CubicV2, EasingDirection::In)],
+            },
+            Keyframe {
+                time: 2.0,
+                poses: vec![pose("A", 2.0)],
+            },
+        ];
+
+        assert!(x_at(&keyframes, "A", 0.9).abs() < 1e-5);
+        assert!((x_at(&keyframes, "A", 1.0) - 1.0).abs() < 1e-5);
+        assert!((x_at(&keyframes, "A", 1.5) - 1.125).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_unsorted_keyframes_and_unknown_bones() {
+        let keyframes = vec![
+            Keyframe {
+                time: 1.0,
+                poses: vec![pose("A", 2.0)],
+            },
+            Keyframe {
+                time: 0.0,
+                poses: vec![pose("A", 0.0)],
+            },
+        ];
+        let sampler = SequenceSampler::new(&keyframes);
+
+        let (position, _) = sampler.sample_bone_glam("A", 0.25).unwrap();
+        assert!((position.x - 0.5).abs() < 1e-5);
+        assert!(sampler.sample_bone("Missing", 0.5).is_none());
+    }
+
+    #[test]
+    fn test_max_deviation_measures_removed_motion() {
+        let original = vec![
+            Keyframe {
+                time: 0.0,
+                poses: vec![pose("A", 0.0)],
+            },
+            Keyframe {
+                time: 0.5,
+                poses: vec![pose("A", 1.0)],
+            },
+            Keyframe {
+                time: 1.0,
+                poses: vec![pose("A", 0.0)],
+            },
+        ];
+        let reduced = vec![original[0].clone(), original[2].clone()];
+
+        let (position, angle) = max_deviation(&original, &original, 30.0);
+        assert!(position < 1e-6 && angle < 1e-3);
+        let (position, angle) = max_deviation(&original, &reduced, 30.0);
+        assert!((position - 1.0).abs() < 1e-5);
+        assert!(angle < 1e-3);
+    }
+}
+
+mod batch_tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use anim2rbx::batch::{
+        BatchInput, OutputClaims, OutputTemplate, collect_inputs, is_animation_file,
+    };
+
+    /// A fresh directory tree of empty files under the system temp directory
+    fn tree(name: &str, files: &[&str]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("anim2rbx-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for file in files {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, b"").unwrap();
+        }
+        root
+    }
+
+    fn input(path: &str, relative: &str) -> BatchInput {
+        BatchInput {
+            path: PathBuf::from(path),
+            relative: PathBuf::from(relative),
+        }
+    }
+
+    #[test]
+    fn test_animation_extensions() {
+        assert!(is_animation_file(Path::new("walk.fbx")));
+        assert!(is_animation_file(Path::new("walk.FBX")));
+        assert!(is_animation_file(Path::new("dir/run.glb")));
+        assert!(!is_animation_file(Path::new("notes.txt")));
+        assert!(!is_animation_file(Path::new("fbx")));
+    }
+
+    #[test]
+    fn test_directories_keep_relative_paths() {
+        let root = tree("dirs", &["walk.fbx", "combat/jab.dae", "notes.txt"]);
+        let inputs = collect_inputs(&[root.display().to_string()]).unwrap();
+
+        let relative: Vec<&Path> = inputs.iter().map(|i| i.relative.as_path()).collect();
+        assert_eq!(
+            relative,
+            [Path::new("combat/jab.dae"), Path::new("walk.fbx")]
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_globs_are_relative_to_their_base_and_deduplicated() {
+        let root = tree("globs", &["a/walk.fbx", "a/b/run.fbx", "a/b/idle.dae"]);
+        let pattern = root.join("a/**/*.fbx").display().to_string();
+        let file = root.join("a/walk.fbx").display().to_string();
+        let inputs = collect_inputs(&[pattern, file]).unwrap();
+
+        let relative: Vec<&Path> = inputs.iter().map(|i| i.relative.as_path()).collect();
+        assert_eq!(relative, [Path::new("b/run.fbx"), Path::new("walk.fbx")]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_overlapping_inputs_keep_their_first_relative_path() {
+        let root = tree("overlap", &["a/walk.fbx", "a/b/run.fbx"]);
+        let specs = [
+            root.join("a/b").display().to_string(),
+            root.join("a").display().to_string(),
+        ];
+        let inputs = collect_inputs(&specs).unwrap();
+
+        let relative: Vec<&Path> = inputs.iter().map(|i| i.relative.as_path()).collect();
+        assert_eq!(relative, [Path::new("run.fbx"), Path::new("walk.fbx")]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_inputs_matching_nothing_are_errors() {
+        let root = tree("empty", &["notes.txt"]);
+        assert!(collect_inputs(&[root.display().to_string()]).is_err());
+        assert!(collect_inputs(&[root.join("*.fbx").display().to_string()]).is_err());
+        assert!(collect_inputs(&[root.join("missing.fbx").display().to_string()]).is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_template_rendering() {
+        let source = input("takes/combat/Hero.fbx", "combat/Hero.fbx");
+        let template = OutputTemplate::new("{stem}_{clip}.{ext}").unwrap();
+
+        assert!(template.uses_clip());
+        assert_eq!(
+            template.render(&source, "Armature|Jab"),
+            "Hero_Armature_Jab.fbx.rbxm"
+        );
+        assert_eq!(
+            template.output_path(Path::new("out"), &source, "Jab"),
+            Path::new("out/combat/Hero_Jab.fbx.rbxm")
+        );
+        assert!(!OutputTemplate::default().uses_clip());
+        assert_eq!(OutputTemplate::default().render(&source, ""), "Hero.rbxm");
+    }
+
+    #[test]
+    fn test_clashing_clip_outputs_are_errors() {
+        let source = input("Hero.fbx", "Hero.fbx");
+        let template = OutputTemplate::new("{stem}_{clip}").unwrap();
+        let out = Path::new("out");
+
+        let outputs = template
+            .output_paths(out, &source, &["Jab", "Kick"])
+            .unwrap();
+        assert_eq!(
+            outputs,
+            [
+                Path::new("out/Hero_Jab.rbxm"),
+                Path::new("out/Hero_Kick.rbxm")
+            ]
+        );
+
+        // Both sanitize to Hero_Armature_Jab.rbxm
+        let clash = template.output_paths(out, &source, &["Armature|Jab", "Armature:Jab"]);
+        assert!(clash.unwrap_err().to_string().contains("Armature:Jab"));
+        assert!(
+            template
+                .output_paths(out, &source, &["Take 001", "Take 001"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_clashes_across_inputs() {
+        let out = Path::new("out");
+        let inputs = [input("walk.fbx", "walk.fbx"), input("walk.dae", "walk.dae")];
+
+        // Same-named inputs clash on every clip they share, unless {ext} tells them apart
+        assert!(
+            OutputTemplate::default()
+                .check_clashes(out, &inputs)
+                .is_err()
+        );
+        let per_clip = OutputTemplate::new("{stem}_{clip}").unwrap();
+        assert!(per_clip.check_clashes(out, &inputs).is_err());
+        let with_ext = OutputTemplate::new("{stem}_{clip}.{ext}").unwrap();
+        assert!(with_ext.check_clashes(out, &inputs).is_ok());
+
+        // Clashes that depend on clip names are caught when the outputs are claimed
+        let claims = OutputClaims::default();
+        let shared = [PathBuf::from("out/a_b_c.rbxm")];
+        let a = input("a.fbx", "a.fbx");
+        let a_b = input("a_b.fbx", "a_b.fbx");
+        claims.claim(&a_b, &shared).unwrap();
+        claims.claim(&a_b, &shared).unwrap();
+        assert!(claims.claim(&a, &shared).is_err());
+        claims.retain(|path| path != Path::new("a_b.fbx"));
+        assert!(claims.claim(&a, &shared).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(OutputTemplate::new("{name}").is_err());
+        assert!(OutputTemplate::new("{stem").is_err());
+        assert!(OutputTemplate::new("dir/{stem}").is_err());
+        assert!(OutputTemplate::new("").is_err());
+    }
+}
+
+mod watch_tests {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant, SystemTime};
+
+    use anim2rbx::watch::{Debouncer, FileState};
+
+    fn state(len: u64, seconds: u64) -> FileState {
+        FileState {
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)),
+            len,
+        }
+    }
+
+    fn files(entries: &[(&str, FileState)]) -> HashMap<PathBuf, FileState> {
+        entries
+            .iter()
+            .map(|(path, state)| (PathBuf::from(path), *state))
+            .collect()
+    }
+
+    #[test]
+    fn test_primed_files_are_not_reported() {
+        let start = Instant::now();
+        let mut debouncer = Debouncer::new(Duration::from_millis(500));
+        debouncer.prime(files(&[("a.fbx", state(10, 1))]));
+
+        let ready = debouncer.update(
+            start + Duration::from_secs(5),
+            files(&[("a.fbx", state(10, 1))]),
+        );
+        assert!(ready.is_empty());
+        assert!(!debouncer.has_pending());
+    }
+
+    #[test]
+    fn test_changes_wait_until_the_file_settles() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut debouncer = Debouncer::new(Duration::from_millis(500));
+        debouncer.prime(files(&[("a.fbx", state(10, 1))]));
+
+        // A write in progress keeps growing the file
+        assert!(
+            debouncer
+                .update(at(0), files(&[("a.fbx", state(20, 2))]))
+                .is_empty()
+        );
+        assert!(
+            debouncer
+                .update(at(300), files(&[("a.fbx", state(40, 2))]))
+                .is_empty()
+        );
+        assert!(
+            debouncer
+                .update(at(700), files(&[("a.fbx", state(40, 2))]))
+                .is_empty()
+        );
+        assert!(debouncer.has_pending());
+
+        let ready = debouncer.update(at(800), files(&[("a.fbx", state(40, 2))]));
+        assert_eq!(ready, [PathBuf::from("a.fbx")]);
+        assert!(
+            debouncer
+                .update(at(2000), files(&[("a.fbx", state(40, 2))]))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_new_files_are_reported_and_removed_files_forgotten() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut debouncer = Debouncer::new(Duration::from_millis(100));
+        debouncer.prime(files(&[("gone.fbx", state(10, 1))]));
+
+        assert!(
+            debouncer
+                .update(at(0), files(&[("new.fbx", state(5, 1))]))
+                .is_empty()
+        );
+        let ready = debouncer.update(at(100), files(&[("new.fbx", state(5, 1))]));
+        assert_eq!(ready, [PathBuf::from("new.fbx")]);
+
+        // A file that comes back after being removed counts as changed
+        assert!(
+            debouncer
+                .update(at(200), files(&[("gone.fbx", state(10, 1))]))
+                .is_empty()
+        );
+        let ready = debouncer.update(at(300), files(&[("gone.fbx", state(10, 1))]));
+        assert_eq!(ready, [PathBuf::from("gone.fbx")]);
+    }
+}
+
+mod inspect_tests {
+    use anim2rbx::inspect::{ChannelInfo, ClipInfo, InspectedNode, Inspection, Problem};
+
+    fn node(name: &str, animated: bool, children: Vec<InspectedNode>) -> InspectedNode {
+        InspectedNode {
+            name: name.to_string(),
+            animated,
+            bone: animated,
+            children,
+        }
+    }
+
+    fn channel(node: &str, keys: usize) -> ChannelInfo {
+        ChannelInfo {
+            node: node.to_string(),
+            position_keys: keys,
+            rotation_keys: keys,
+            scaling_keys: 1,
+            scale_animated: false,
+        }
+    }
+
+    fn clip(name: &str, ticks_per_second: f64, channels: Vec<ChannelInfo>) -> ClipInfo {
+        ClipInfo {
+            name: name.to_string(),
+            duration: 30.0 / ticks_per_second.max(24.0),
+            duration_ticks: 30.0,
+            ticks_per_second,
+            channels,
+        }
+    }
+
+    fn hierarchy() -> InspectedNode {
+        node(
+            "RootNode",
+            false,
+            vec![node(
+                "Hips",
+                true,
+                vec![
+                    node(
+                        "Arm_$AssimpFbx$_Rotation",
+                        false,
+                        vec![node("Arm", true, vec![])],
+                    ),
+                    node("Leg", true, vec![]),
+                ],
+            )],
+        )
+    }
+
+    #[test]
+    fn test_clean_file_has_no_problems() {
+        let inspection = Inspection::new(
+            vec![clip(
+                "Walk",
+                30.0,
+                vec![channel("Hips", 31), channel("Leg", 31)],
+            )],
+            Some(node("Hips", true, vec![node("Leg", true, vec![])])),
+        );
+        assert!(inspection.problems.is_empty());
+    }
+
+    #[test]
+    fn test_walk_is_depth_first() {
+        let root = hierarchy();
+        let walked: Vec<(usize, &str)> = root
+            .walk()
+            .into_iter()
+            .map(|(depth, node)| (depth, node.name.as_str()))
+            .collect();
+        assert_eq!(
+            walked,
+            [
+                (0, "RootNode"),
+                (1, "Hips"),
+                (2, "Arm_$AssimpFbx$_Rotation"),
+                (3, "Arm"),
+                (2, "Leg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_problems_are_detected() {
+        let mut scaled = channel("Leg", 10);
+        scaled.scale_animated = true;
+        let inspection = Inspection::new(
+            vec![
+                clip("Take 001", 0.0, vec![channel("Hips", 10), scaled]),
+                clip("Take 001", 30.0, vec![channel("Tail", 10)]),
+                clip("Empty", 30.0, Vec::new()),
+            ],
+            Some(hierarchy()),
+        );
+
+        assert_eq!(
+            inspection.problems,
+            [
+                Problem::UnsetTicksPerSecond {
+                    animation: "Take 001".to_string()
+                },
+                Problem::DuplicateAnimation {
+                    animation: "Take 001".to_string()
+                },
+                Problem::EmptyAnimation {
+                    animation: "Empty".to_string()
+                },
+                Problem::HelperNode {
+                    node: "Arm_$AssimpFbx$_Rotation".to_string(),
+                    owner: "Arm".to_string()
+                },
+                Problem::AnimatedScale {
+                    animation: "Take 001".to_string(),
+                    node: "Leg".to_string()
+                },
+                Problem::MissingNode {
+                    animation: "Take 001".to_string(),
+                    node: "Tail".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_animations() {
+        let inspection = Inspection::new(Vec::new(), None);
+        assert_eq!(inspection.problems, [Problem::NoAnimations]);
+    }
+
+    #[test]
+    fn test_json_output() {
+        let inspection = Inspection::new(
+            vec![clip("Walk", 30.0, vec![channel("Tail", 2)])],
+            Some(node("Hips", true, vec![])),
+        );
+        let json = serde_json::to_value(&inspection).unwrap();
+
+        assert_eq!(json["clips"][0]["name"], "Walk");
+        assert_eq!(json["clips"][0]["channels"][0]["position_keys"], 2);
+        assert_eq!(json["root"]["name"], "Hips");
+        assert_eq!(json["root"]["animated"], true);
+        assert_eq!(json["problems"][0]["kind"], "missing_node");
+        assert_eq!(json["problems"][0]["node"], "Tail");
+    }
+}